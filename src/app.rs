@@ -6,30 +6,160 @@ use color_eyre::{
 
 use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
 
-use num::ToPrimitive;
-use rand::{thread_rng, Rng};
 use ratatui::{
     prelude::*, 
     style::Color, 
-    widgets::{block::*, canvas::{Canvas, Rectangle}, Paragraph, *}
+    widgets::{block::*, Paragraph, *}
 };
 
-use std::{path::Path, thread};
+use std::cmp::Ordering;
+use std::time::{Duration, Instant};
+
+use tetris::bot::{Bot, Difficulty};
+use tetris::config::GameConfig;
+use tetris::game::{Game, GameEvent, GameOver, TICK, TICKS_PER_SECOND};
+use tetris::history::History;
+use tetris::input::{Action, Input};
+use tetris::mode::GameMode;
+use tetris::piece::PieceKind;
+use tetris::protocol::{self, Message};
+use tetris::replay::{Playback, Replay, TimedInput};
+use tetris::scoring::ScoreAction;
+
+use crate::keys::KeyTracker;
+use crate::leaderboard::{format_date, format_duration, Entry, Leaderboard, PersonalBests, MAX_NAME_LEN};
+use crate::net::{Connection, Spectators};
+use crate::settings::Settings;
+use crate::read_write::*;
+use crate::view::GameView;
+
+const MAX_TICKS_PER_FRAME: u32 = 10;
+
+const REPLAY_SPEEDS: [f64; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+const NORMAL_SPEED: usize = 2;
+// how far the arrow keys jump in a replay
+const SEEK_TICKS: u64 = 5 * TICKS_PER_SECOND as u64;
+const DEMO_DIFFICULTY: Difficulty = Difficulty::Medium;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Screen {
+    // the computer plays a demo until a key starts the game
+    Title,
+    Game,
+    // the finished run made the leaderboard and waits for a name
+    NameEntry { entry: Entry, rank: usize },
+    // `highlight` is the rank of a run that was just added
+    Leaderboard { mode: GameMode, highlight: Option<usize> },
+}
 
-use std::time::Duration;
+// The second player of a versus game, on the same seed as the first.
+#[derive(Debug)]
+struct Opponent {
+    game: Game,
+    keys: KeyTracker,
+    last_action: Option<(ScoreAction, u64)>,
+    // set when the computer plays the opponent
+    bot: Option<Bot>,
+}
 
-use crate::read_write::*;
+impl Opponent {
+
+    fn new(config: &GameConfig, bot: Option<Difficulty>) -> Opponent {
+        Opponent {
+            game: Game::new(config),
+            keys: KeyTracker::new(tui::has_release_events()),
+            last_action: None,
+            bot: bot.map(|difficulty| Bot::new(difficulty, config.seed)),
+        }
+    }
+}
+
+// The computer playing marathon by itself on the title screen.
+#[derive(Debug)]
+struct Demo {
+    game: Game,
+    bot: Bot,
+}
+
+impl Demo {
+
+    fn new() -> Demo {
+        let config = GameConfig {
+            seed: rand::random(),
+            ..GameConfig::default()
+        };
+        Demo {
+            game: Game::new(&config),
+            bot: Bot::new(DEMO_DIFFICULTY, config.seed),
+        }
+    }
+
+    // Plays on, a new game starts once the bot tops out.
+    fn tick(&mut self) {
+        if self.game.is_over() {
+            *self = Demo::new();
+        }
+        for input in self.bot.inputs(&self.game) {
+            self.game.input(input);
+        }
+        self.game.tick();
+        self.game.take_events();
+    }
+}
 
-#[derive(Debug, Default)]
+// A versus game against another instance over the network. The opponent is the copy of
+// the other player's game, it only advances with the messages that come in.
+// A spectator has copies of both games, the host's in `game` and the guest's as the opponent.
+#[derive(Debug)]
+struct Online {
+    connection: Connection,
+    // set on the host, the games are relayed to them
+    spectators: Option<Spectators>,
+    // the players asked for another game, this one and the other
+    rematch: bool,
+    remote_rematch: bool,
+    // last tick of the game the other player was told about
+    sent_tick: u64,
+    // the games known to have stopped for good, this one and the other's,
+    // for a spectator the host's and the guest's
+    ended: [bool; 2],
+}
+
+impl Online {
+
+    // Tells the other player and the spectators about this game.
+    fn send(&mut self, message: Message) {
+        if let Some(spectators) = &mut self.spectators {
+            spectators.relay(0, &message);
+        }
+        self.connection.send(&message);
+    }
+}
+
+#[derive(Debug)]
 pub struct App {
-    pub score: u64,
     pub highscore: u64,
     exit: bool,
     on_pause: bool,
-    dead: bool,
-    current_piece: Piece,
-    pieces: Vec<Piece>,
-    next_piece: Piece,
+    game: Game,
+    settings: Settings,
+    keys: KeyTracker,
+    last_action: Option<(ScoreAction, u64)>,
+    // inputs of the game being played
+    recording: Replay,
+    // set when watching a replay instead of playing
+    playback: Option<Playback>,
+    speed: usize,
+    leaderboard: Leaderboard,
+    personal_bests: PersonalBests,
+    // undo and redo of placements in practice mode
+    history: Option<History>,
+    // set in a versus game, `game` is the first player then
+    opponent: Option<Opponent>,
+    online: Option<Online>,
+    // set while the title screen is up
+    demo: Option<Demo>,
+    screen: Screen,
     padding: f64,
 }
 
@@ -38,761 +168,1034 @@ impl Widget for &App {
         where
             Self: Sized {
 
-                let fg_color: Color;
-                let bg_color: Color;
-
-                if self.dead {
-                    fg_color = Color::Red;
-                    bg_color = Color::Black;
-                }
-                else {
-                    fg_color = Color::White;
-                    bg_color = Color::Black;
-                }
+                let fg_color = if self.is_over() { Color::Red } else { Color::White };
+                let bg_color = Color::Black;
 
-                let block = Block::default()
+                let mut block = Block::default()
                                 .borders(Borders::ALL)
                                 .border_style(Style::default().bold())
                                 .title(Title::from(" Tetris ".bold())
                                         .alignment(Alignment::Center))
                                 .bg(bg_color)
                                 .fg(fg_color);
+                if let Some(playback) = &self.playback {
+                    block = block.title(Title::from(self.replay_status(playback))
+                            .alignment(Alignment::Center)
+                            .position(Position::Bottom));
+                }
+                else if self.history.is_some() {
+                    block = block.title(Title::from(" u undo  r redo  1-7 next piece  Backspace clear ")
+                            .alignment(Alignment::Center)
+                            .position(Position::Bottom));
+                }
+                else if let Some(online) = &self.online {
+                    block = block.title(Title::from(self.online_status(online))
+                            .alignment(Alignment::Center)
+                            .position(Position::Bottom));
+                }
+                else if self.opponent.as_ref().is_some_and(|opponent| opponent.bot.is_none()) {
+                    block = block.title(Title::from(" P1 ad s w e r Space f   P2 arrows . , / Enter ")
+                            .alignment(Alignment::Center)
+                            .position(Position::Bottom));
+                }
+                let inner = block.inner(area);
+                block.render(area, buf);
 
-                let score_text = Line::from(self.score.to_string().bold());        
-                let highscore_text = Line::from(self.highscore.to_string().bold());
-
-                Paragraph::new(Line::from(" Next Piece              "))
-                    .block(block.clone())
-                    .right_aligned()
-                    .render(area, buf);
-
-                Paragraph::new(score_text)
-                    .block(block.clone())
-                    .right_aligned()
-                    .render(area, buf);
-
-                Paragraph::new(highscore_text)
-                    .block(block.clone())
-                    .left_aligned()
-                    .render(area, buf);
-
-                if self.dead {
-                    let death_text = Line::from(vec![Span::from(" You died with score "), Span::from(self.score.to_string().bold())]);
-                    Paragraph::new(death_text)
-                    .block(block.clone())
-                    .alignment(Alignment::Center)
-                    .render(area, buf);
-
+                if let Screen::Leaderboard { mode, highlight } = &self.screen {
+                    self.render_leaderboard(*mode, *highlight, inner, buf);
+                    return;
                 }
-                
-                if self.on_pause {
-                    let death_text = Line::from(vec![Span::from(" Paused "), Span::from(self.score.to_string().bold())]);
-                    Paragraph::new(death_text)
-                    .block(block.clone())
-                    .centered()
-                    .render(area, buf);
 
+                if let (Screen::Title, Some(demo)) = (&self.screen, &self.demo) {
+                    self.render_title(demo, inner, buf);
+                    return;
                 }
 
-                if !self.dead {
-                    Canvas::default()
-                        .block(block.clone())
-                        .x_bounds([-180.0, 180.0])
-                        .y_bounds([-90.0, 90.0])
-                        .background_color(Color::Black)
-                        .paint(|ctx| {
-                            ctx.draw(&Rectangle {
-                                x: -70.0, 
-                                y: -90.0,
-                                width: 140.0,
-                                height: 180.0,
-                                color: Color::White,
-                            });
-                            ctx.layer();
-                            for component in self.current_piece.components.iter() {
-                                ctx.draw(&Rectangle {
-                                    x: component.x + self.padding,
-                                    y: component.y + self.padding,
-                                    width: component.width - self.padding,
-                                    height: component.height - self.padding,
-                                    color: self.current_piece.color
-                                });
-                            }
-                            ctx.layer();
-                            for piece in self.pieces.iter() {
-                                for component in piece.components.iter() {
-                                    ctx.draw(&Rectangle {
-                                        x: component.x + self.padding,
-                                        y: component.y + self.padding,
-                                        width: component.width - self.padding,
-                                        height: component.height - self.padding,
-                                        color: piece.color
-                                    });
-                                }
-                            }
-                            ctx.layer();
-                            for component in self.next_piece.components.iter() {
-                                ctx.draw(&Rectangle {
-                                    x: component.x + self.padding,
-                                    y: component.y + self.padding,
-                                    width: component.width - self.padding,
-                                    height: component.height - self.padding,
-                                    color: self.next_piece.color
-                                });
-                            }
-                        })
-                        .render(area, buf);
-
-                    if self.on_pause {
-                        Paragraph::new(Line::from("Paused"))
-                            .block(block.clone())
-                            .centered()
-                            .bold()
-                            .render(area, buf);
+                if let Some(opponent) = &self.opponent {
+                    let [one, two] = Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)]).areas(inner);
+                    let titles = if self.is_watching() {
+                        [" Host ", " Guest "]
                     }
+                    else if self.online.is_some() {
+                        [" You ", " Opponent "]
+                    }
+                    else if opponent.bot.is_some() {
+                        [" You ", " Computer "]
+                    }
+                    else {
+                        [" Player 1 ", " Player 2 "]
+                    };
+                    self.view(&self.game, Some(&opponent.game), &self.last_action).title(titles[0]).render(one, buf);
+                    self.view(&opponent.game, Some(&self.game), &opponent.last_action).title(titles[1]).render(two, buf);
+                    return;
+                }
+
+                let view = self.view(&self.game, None, &self.last_action);
+                let [_, field, _] = view.areas(inner);
+                view.render(inner, buf);
+
+                if let Screen::NameEntry { entry, rank } = &self.screen {
+                    self.render_name_entry(entry, *rank, field, buf);
                 }
     }   
 }
 
 impl App {
 
-    pub fn run(&mut self, terminal: &mut tui::Tui) -> Result<()> {
-        loop {
-            terminal.draw(|frame| self.render_frame(frame))?;
-            let time = 500000;
-            if event::poll(Duration::from_micros(time))? {
-                self.handle_events().wrap_err("handle events failed")?;
-                thread::sleep(Duration::from_micros(50000));
-            }
-            if self.exit {
-                break;
-            }
-            if self.on_pause || self.dead {
-                continue;
-            }
-            self.handle_piece()?;
-            self.highscore();
-            self.is_dead()?;
+    // The view of one of the players, `self.game` unless it is the opponent of a versus game.
+    // `other` is the game of the other player of a versus game.
+    fn view<'a>(&'a self, game: &'a Game, other: Option<&Game>, last_action: &'a Option<(ScoreAction, u64)>) -> GameView<'a> {
+        let mut view = GameView::new(game)
+            .stats(self.stats(game))
+            .last_action(last_action.as_ref())
+            .paused(self.on_pause)
+            .padding(self.padding);
+        if self.is_over() {
+            view = view.end_text(self.end_text(game, other));
         }
-        Ok(())
+        view
     }
 
-    fn render_frame(&self, frame: &mut Frame) {
-        frame.render_widget(self, frame.size());
+    // The boxes of the side panel below the hold piece, they depend on the mode.
+    fn stats(&self, game: &Game) -> Vec<(&'static str, String)> {
+        let mode = game.mode();
+        let best = self.personal_bests.get(mode);
+        match mode {
+            GameMode::Marathon => vec![
+                (" Score ", game.score().to_string()),
+                (" Highscore ", self.highscore.to_string()),
+                (" Level ", game.level().to_string()),
+                (" Lines ", game.lines().to_string()),
+            ],
+            GameMode::Sprint => vec![
+                (" Time ", format_duration(game.time_ms())),
+                (" Best ", best.map_or("-".to_string(), |best| format_duration(best.duration_ms))),
+                (" Lines left ", game.lines_left().unwrap_or(0).to_string()),
+                (" PPS ", format!("{:.2}", game.pps())),
+            ],
+            GameMode::Dig => vec![
+                (" Time ", format_duration(game.time_ms())),
+                (" Best ", best.map_or("-".to_string(), |best| format_duration(best.duration_ms))),
+                (" Garbage ", game.garbage_left().unwrap_or(0).to_string()),
+                (" PPS ", format!("{:.2}", game.pps())),
+            ],
+            GameMode::Zen => vec![
+                (" Score ", game.score().to_string()),
+                (" Lines ", game.lines().to_string()),
+                (" Pieces ", game.pieces().to_string()),
+                (" PPS ", format!("{:.2}", game.pps())),
+            ],
+            GameMode::Ultra => vec![
+                (" Time left ", format_duration(game.time_left_ms().unwrap_or(0))),
+                (" Score ", game.score().to_string()),
+                (" Best ", best.map_or("-".to_string(), |best| best.score.to_string())),
+                (" Lines ", game.lines().to_string()),
+            ],
+            GameMode::Versus => vec![
+                (" Lines ", game.lines().to_string()),
+                (" Sent ", game.garbage_sent().to_string()),
+                (" Level ", game.level().to_string()),
+                (" PPS ", format!("{:.2}", game.pps())),
+            ],
+        }
     }
 
-    fn highscore(&mut self) {
-        if self.score > self.highscore {
-            self.highscore = self.score;
+    // Whether `game` of a versus game outlasted `other`, None until both games stopped for good.
+    // Online, a copy may still be behind the game it follows, so that takes the Ends of both.
+    fn outcome(&self, game: &Game, other: &Game) -> Option<Ordering> {
+        if self.online.as_ref().is_some_and(|online| online.ended != [true, true]) {
+            return None;
+        }
+        match (game.is_over(), other.is_over()) {
+            // the game that ended first lost
+            (true, true) => Some(game.ticks().cmp(&other.ticks())),
+            (true, false) => Some(Ordering::Less),
+            (false, true) => Some(Ordering::Greater),
+            (false, false) => None,
         }
     }
 
-    fn handle_events(&mut self) -> Result<()> {
-        match event::read()? {
-            Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
-                self.handle_key_event(key_event).wrap_err_with(|| {
-                    format!("handling key event failed: \n{key_event:#?}")
-                })
-            }
-           _ => Ok(())
+    // What the game over screen shows below the result.
+    fn breakdown(&self, game: &Game) -> Vec<Line<'static>> {
+        if !game.mode().has_time_limit() {
+            return vec![];
         }
+        let stats = game.score_stats();
+        [
+            ("Singles", stats.singles),
+            ("Doubles", stats.doubles),
+            ("Triples", stats.triples),
+            ("Tetrises", stats.tetrises),
+            ("T-spins", stats.t_spins),
+            ("T-spin minis", stats.t_spin_minis),
+            ("Back-to-backs", stats.back_to_backs),
+            ("Perfect clears", stats.perfect_clears),
+            ("Max combo", stats.max_combo),
+        ]
+        .into_iter()
+        .map(|(name, count)| Line::from(vec![Span::from(format!("{name:<15}")), count.to_string().bold()]))
+        .collect()
     }
 
-    pub fn new() -> Result<App> {
-        let mut app = App {
-            score: 0,
-            highscore: 0,
-            exit: false,
-            dead: false,
-            on_pause: false,
-            current_piece: Piece::placeholder(), // make these random
-            pieces: vec![],
-            next_piece: Piece::placeholder(),
-            padding: 0.0, // 2.0 seems good
+    // Shown instead of the field once the game is over.
+    fn end_text(&self, game: &Game, other: Option<&Game>) -> Vec<Line<'static>> {
+        if game.mode().is_versus() {
+            let watching = self.is_watching();
+            let result = match other.and_then(|other| self.outcome(game, other)) {
+                Some(Ordering::Less) => if watching { " Out " } else { " You lose " },
+                Some(Ordering::Greater) => if watching { " Winner " } else { " You win " },
+                Some(Ordering::Equal) => " Draw ",
+                None if self.online.as_ref().is_some_and(|online| online.connection.is_closed()) => " Connection lost ",
+                None => " Waiting for the result ",
+            };
+            return vec![
+                Line::from(result.bold()),
+                Line::from(vec![Span::from(" Sent "), game.garbage_sent().to_string().bold(), Span::from(" lines ")]),
+                Line::from(vec![Span::from(" Seed "), game.seed().to_string().bold()]),
+            ];
+        }
+        let finished = game.game_over() == Some(GameOver::Finished);
+        let result = if finished && game.mode().ranks_by_time() {
+            Line::from(vec![Span::from(" Finished in "), format_duration(game.time_ms()).bold()])
+        }
+        else if finished {
+            Line::from(vec![Span::from(" Time's up with score "), game.score().to_string().bold()])
+        }
+        else {
+            Line::from(vec![Span::from(" You died with score "), game.score().to_string().bold()])
         };
-        app.init_queue()?;
-        app.next_piece()?;
-        Ok(app)
-    }
-
-    fn handle_key_event(&mut self, key_event: KeyEvent) -> Result<()> {
-        match key_event.code {
-            KeyCode::Char('q') => self.exit(),
-            KeyCode::Esc => self.pause()?,
-            KeyCode::Enter => self.restart()?,
-            KeyCode::Right => self.move_current_right()?,
-            KeyCode::Left => self.move_current_left()?,
-            //KeyCode::Down => self.move_current_down()?,
-            KeyCode::Up => self.rotate_current()?,
-            _ => {}
+        let mut death_text = vec![
+            result,
+            Line::from(vec![Span::from(" Seed "), game.seed().to_string().bold()]),
+        ];
+        let breakdown = self.breakdown(game);
+        if !breakdown.is_empty() {
+            death_text.push(Line::from(""));
+            death_text.extend(breakdown.into_iter().map(|line| line.left_aligned()));
         }
-        Ok(())
+        death_text
     }
 
-    fn restart(&mut self) -> Result<()> {
-
-        if self.dead {
-            let path = Path::new("Highscore.bin");
-            save(path, self.highscore)?;
-            
-            let num = read(path)?;
-
-            self.highscore = num;
-            self.score = 0;
-            self.on_pause = false;
-            self.dead = false;
-            self.pieces = vec![];
-            self.next_piece()?;
-        }
-
-        Ok(())
+    fn render_name_entry(&self, entry: &Entry, rank: usize, area: Rect, buf: &mut Buffer) {
+        let [_, popup, _] = Layout::vertical([Constraint::Fill(1), Constraint::Length(6), Constraint::Fill(1)])
+            .areas(area);
+        let text = vec![
+            Line::from(if self.game.mode().ranks_by_time() {
+                format!("Rank {} in {}", rank + 1, format_duration(entry.duration_ms))
+            }
+            else {
+                format!("Rank {} with {}", rank + 1, entry.score)
+            }),
+            Line::from(vec![Span::from("Name: "), entry.name.clone().bold(), "_".slow_blink()]),
+            Line::from(""),
+            Line::from("Enter save  Esc skip".dark_gray()),
+        ];
+        Clear.render(popup, buf);
+        Paragraph::new(text)
+            .block(Block::bordered().title(" New record ").title_alignment(Alignment::Center).yellow())
+            .centered()
+            .white()
+            .render(popup, buf);
     }
 
-    fn exit(&mut self) {
-        self.exit = true;
+    // The demo game with what Enter would start on top of it.
+    fn render_title(&self, demo: &Demo, area: Rect, buf: &mut Buffer) {
+        let view = GameView::new(&demo.game)
+            .stats(vec![
+                (" Lines ", demo.game.lines().to_string()),
+                (" PPS ", format!("{:.2}", demo.game.pps())),
+            ])
+            .padding(self.padding);
+        let [_, field, _] = view.areas(area);
+        view.render(area, buf);
+
+        let mode = self.settings.game.mode;
+        let game = match self.settings.bot {
+            Some(difficulty) if mode.is_versus() => format!("{} against the computer, {}", mode.title(), difficulty.name()),
+            _ => mode.title().to_string(),
+        };
+        let [_, popup, _] = Layout::vertical([Constraint::Fill(1), Constraint::Length(7), Constraint::Fill(1)])
+            .areas(field);
+        let text = vec![
+            Line::from(game.bold()),
+            Line::from(""),
+            Line::from("Enter play"),
+            Line::from("l leaderboard  q quit".dark_gray()),
+        ];
+        Clear.render(popup, buf);
+        Paragraph::new(text)
+            .block(Block::bordered().title(" Tetris ").title_alignment(Alignment::Center).yellow())
+            .centered()
+            .wrap(Wrap { trim: true })
+            .white()
+            .render(popup, buf);
     }
 
-    fn pause(&mut self) -> Result<()> {
-        if self.on_pause {
-            self.on_pause = false;
-        }
-        else {
-            self.on_pause = true;
-        }
-        Ok(())
+    fn render_leaderboard(&self, mode: GameMode, highlight: Option<usize>, area: Rect, buf: &mut Buffer) {
+        let header = Row::new(["#", "Name", "Score", "Lines", "Level", "Time", "PPS", "Date", "Seed"]).bold();
+        let rows = self.leaderboard.entries(mode).iter().enumerate().map(|(rank, entry)| {
+            let row = Row::new([
+                (rank + 1).to_string(),
+                entry.name.clone(),
+                entry.score.to_string(),
+                entry.lines.to_string(),
+                entry.level.to_string(),
+                format_duration(entry.duration_ms),
+                format!("{:.2}", entry.pps()),
+                format_date(entry.date),
+                entry.seed.to_string(),
+            ]);
+            if highlight == Some(rank) { row.yellow().bold() } else { row }
+        });
+        let widths = [
+            Constraint::Length(3),
+            Constraint::Length(MAX_NAME_LEN as u16),
+            Constraint::Length(10),
+            Constraint::Length(6),
+            Constraint::Length(6),
+            Constraint::Length(10),
+            Constraint::Length(5),
+            Constraint::Length(10),
+            Constraint::Length(20),
+        ];
+        let title = format!(" Leaderboard: {} ", mode.title());
+        let [table, help] = Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(area);
+        let table_widget = Table::new(rows, widths)
+            .header(header)
+            .block(Block::bordered().title(title).title_alignment(Alignment::Center));
+        Widget::render(table_widget, table, buf);
+        Paragraph::new(Line::from("Left/Right mode  Esc back".dark_gray()))
+            .centered()
+            .render(help, buf);
     }
 
-    fn is_dead(&mut self) -> Result<()> {
-        if self.pieces.iter().map(|piece| {
-            piece.max_y >= 80.0
-        }).any(|x| x) {
-            self.dead = true;
+    fn online_status(&self, online: &Online) -> Line<'static> {
+        let status = if self.is_watching() {
+            if online.connection.is_closed() {
+                " The host left  q quit "
+            }
+            else if self.is_over() {
+                " Waiting for the next game  q quit "
+            }
+            else {
+                " Watching  q quit "
+            }
+            .to_string()
         }
-        Ok(())
-    }
-
-    fn row_clear(&mut self, min: f64, max: f64) -> Result<()> {
-        let mut deleted_rows = vec![];
-        let start = (min / 10.0).to_i8().unwrap();
-        let stop = (max / 10.0).to_i8().unwrap();
-        for i in start..(stop + 1) {
-            let row = Piece::whole_line((10 * i).to_f64().unwrap());
-            if row.components.iter().map(|cmp| {
-                self.pieces.iter().map(|piece| {
-                    piece.is_blocked(cmp)
-                }).any(|x| x)
-            }).all(|x| x) { // if the piece is manually moved in the moment it hits the bottom this evauates to false somehow
-                self.delete_row((10 * i).to_f64().unwrap())?;
-                self.score += 1000;
-                deleted_rows.push((10 * i).to_f64().unwrap());
+        else if online.connection.is_closed() {
+            " The other player left  q quit ".to_string()
+        }
+        else if !self.is_over() {
+            // the copy of the other game is behind by about the time messages take
+            let behind = self.opponent.as_ref().map_or(0, |opponent| self.game.ticks().saturating_sub(opponent.game.ticks()));
+            let watching = online.spectators.as_ref().map_or(0, |spectators| spectators.len());
+            if watching > 0 {
+                format!(" Online  lag {} ms  {watching} watching ", behind * 1000 / TICKS_PER_SECOND as u64)
+            }
+            else {
+                format!(" Online  lag {} ms ", behind * 1000 / TICKS_PER_SECOND as u64)
             }
         }
-        deleted_rows.reverse();
-        if deleted_rows.len() > 1 {
-            self.score += 1000 * deleted_rows.len().to_u64().unwrap();
+        else if online.rematch {
+            " Waiting for the other player ".to_string()
         }
-        for val in deleted_rows.iter() {
-            self.gravity(*val)?;
+        else if online.remote_rematch {
+            " The other player wants a rematch  Enter play again ".to_string()
         }
+        else {
+            " Enter rematch  q quit ".to_string()
+        };
+        Line::from(status)
+    }
 
-        Ok(())
+    fn replay_status(&self, playback: &Playback) -> Line<'static> {
+        let state = if self.on_pause { "paused" } else { "playing" };
+        Line::from(vec![
+            Span::from(" Replay "),
+            format_ticks(self.game.ticks()).bold(),
+            Span::from(format!(" / {}  {}x  {} ", format_ticks(playback.replay().ticks), REPLAY_SPEEDS[self.speed], state)),
+        ])
     }
 
-    fn delete_row(&mut self, row: f64) -> Result<()> {
-        for piece in self.pieces.iter_mut() {
-            if piece.max_y < row || piece.min_y > row {
-                continue;
+    pub fn run(&mut self, terminal: &mut tui::Tui) -> Result<()> {
+        let frame_time = Duration::from_secs(1) / self.settings.frame_rate.max(1);
+        let mut previous = Instant::now();
+        let mut lag = Duration::ZERO;
+        loop {
+            let frame_start = Instant::now();
+            let speed = REPLAY_SPEEDS[self.speed];
+            lag += (frame_start - previous).mul_f64(speed);
+            previous = frame_start;
+
+            while event::poll(Duration::ZERO)? {
+                self.handle_events().wrap_err("handle events failed")?;
+            }
+            if self.exit {
+                break;
             }
-            let mut count = 0;
-            for (i, cmp) in piece.components.clone().iter().enumerate() {
-                if cmp.y == row {
-                    piece.components.remove(i - count);
-                    count += 1;
+            self.receive();
+            for input in self.keys.expire(frame_start) {
+                self.input(input);
+            }
+            let released = self.opponent.as_mut().map_or(vec![], |opponent| opponent.keys.expire(frame_start));
+            for input in released {
+                self.player_input(1, input);
+            }
+
+            // don't try to catch up after the process was suspended for a long time
+            lag = lag.min((TICK * MAX_TICKS_PER_FRAME).mul_f64(speed.max(1.0)));
+            while lag >= TICK {
+                if !self.on_pause {
+                    self.tick();
                 }
+                lag -= TICK;
+            }
+            self.handle_game_events();
+            self.send_progress();
+
+            terminal.draw(|frame| self.render_frame(frame))?;
+
+            // wait for the next frame, but wake up as soon as there is input
+            let elapsed = frame_start.elapsed();
+            if elapsed < frame_time {
+                event::poll(frame_time - elapsed)?;
+            }
+        }
+        if self.playback.is_none() && !self.game.is_over() {
+            self.save_replay()?;
+        }
+        if let Some(online) = &mut self.online {
+            online.connection.close();
+            if let Some(spectators) = &mut online.spectators {
+                spectators.close();
             }
         }
         Ok(())
     }
 
-    fn gravity(&mut self, y: f64) -> Result<()> {
-        for piece in self.pieces.iter_mut() {
-            for cmp in piece.components.iter_mut() {
-                if cmp.y < y {
-                    continue;
+    fn tick(&mut self) {
+        // the game waits for the title screen to go away
+        if let Some(demo) = &mut self.demo {
+            demo.tick();
+            return;
+        }
+        if let Some(playback) = &mut self.playback {
+            playback.step(&mut self.game);
+            return;
+        }
+        // a versus game stops for both players as soon as one of them is out,
+        // and the games a spectator watches only advance with the messages of the players
+        if self.is_over() || self.is_watching() {
+            return;
+        }
+        self.game.tick();
+        // the copy of the game of an online opponent only advances with its messages
+        if let Some(opponent) = self.opponent.as_mut().filter(|_| self.online.is_none()) {
+            if let Some(bot) = &mut opponent.bot {
+                for input in bot.inputs(&opponent.game) {
+                    opponent.game.input(input);
                 }
-                cmp.y -= 10.0;
             }
+            opponent.game.tick();
         }
-        Ok(())
     }
 
-    fn handle_piece(&mut self) -> Result<()> {
-        self.move_current_down()?;
-        if self.current_piece_at_bottom()? {
-            self.pieces.push(self.current_piece.clone());
-            self.row_clear(self.current_piece.min_y, self.current_piece.max_y)?;
-            self.next_piece()?;
-        }
-        Ok(())
+    // A versus game is over as soon as one of the players is, or the other player left.
+    fn is_over(&self) -> bool {
+        self.game.is_over()
+            || self.opponent.as_ref().is_some_and(|opponent| opponent.game.is_over())
+            || self.online.as_ref().is_some_and(|online| online.connection.is_closed())
     }
 
-    fn current_piece_at_bottom(&mut self) -> Result<bool> {
-        let mut current_piece = self.current_piece.clone();
-        current_piece.move_down()?;
-        Ok(current_piece.components.iter().map(|cmp| {
-            self.pieces.iter().map(|piece| {
-                piece.is_blocked(cmp)
-            }).any(|x| x)
-        }).any(|x| x) || self.current_piece.min_y == -90.0)
+    // Spectating the games of a host instead of playing.
+    fn is_watching(&self) -> bool {
+        self.online.as_ref().is_some_and(|online| online.connection.is_spectator())
     }
 
-    fn next_piece(&mut self) -> Result<()> {
-        self.current_piece = self.next_piece.clone();
-        let mut rng = thread_rng();
-        let random_num = rng.gen_range(0..4);
-        let colors = vec![Color::White, Color::Cyan, Color::Yellow, Color::Red, Color::Blue, Color::Magenta, Color::Green];
-        if random_num == 0 {
-            self.next_piece = Piece::long();
+    // Hands an input to the game and records it for the replay.
+    fn input(&mut self, input: Input) {
+        if self.playback.is_some() || self.is_over() || self.is_watching() {
+            return;
         }
-        else if random_num == 1 {
-            self.next_piece = Piece::square();
+        self.recording.record(self.game.ticks(), input);
+        if let Some(online) = &mut self.online {
+            online.send(Message::Input(TimedInput { tick: self.game.ticks(), input }));
         }
-        else if random_num == 2 {
-            self.next_piece = Piece::t_piece(); 
+        self.game.input(input);
+    }
+
+    // Like `input`, for either player of a versus game. Player 0 is `self.game`.
+    fn player_input(&mut self, player: usize, input: Input) {
+        if player == 0 {
+            self.input(input);
+            return;
         }
-        else if random_num == 3 {
-            self.next_piece = Piece::l_piece();
+        if self.is_over() {
+            return;
         }
-        self.current_piece.set_center();
-        for _ in 0..rng.gen_range(0..3) {
-            self.rotate_current()?;
+        if let Some(opponent) = &mut self.opponent {
+            opponent.game.input(input);
         }
-        self.next_piece.color = colors[rng.gen_range(0..colors.len())];
+    }
 
-        for _ in 0..12 {
-            self.next_piece.move_right(true)?;
-            self.current_piece.move_left(true)?;
+    fn keys_of(&mut self, player: usize) -> &mut KeyTracker {
+        match &mut self.opponent {
+            Some(opponent) if player == 1 => &mut opponent.keys,
+            _ => &mut self.keys,
         }
-        for _ in 0..3 {
-            self.next_piece.move_down()?;
-            self.current_piece.move_up()?;
-        }
-        Ok(())
     }
 
-    fn init_queue(&mut self) -> Result<()> {
-        let mut rng = thread_rng();
-        let random_num = rng.gen_range(0..=4);
-        let colors = vec![Color::White, Color::Cyan, Color::Yellow, Color::Red, Color::Blue, Color::Magenta, Color::Green];
-        if random_num == 0 {
-            self.next_piece = Piece::long();
+    // The player and action of a key, a versus game splits the keyboard in two.
+    fn key_action(&self, code: KeyCode) -> Option<(usize, Action)> {
+        // against the computer the keyboard is all yours
+        if self.opponent.as_ref().is_some_and(|opponent| opponent.bot.is_none()) && self.online.is_none() {
+            versus_key_action(code)
         }
-        else if random_num == 1 {
-            self.next_piece = Piece::square();
-        }
-        else if random_num == 2 {
-            self.next_piece = Piece::t_piece(); 
+        else {
+            key_action(code).map(|action| (0, action))
         }
-        else if random_num == 3 {
-            let random_num_for_orientation = rng.gen_range(0.0..1.0);
-            if random_num_for_orientation < 0.5 {
-                self.next_piece = Piece::inverted_l_piece();
-            }
-            else {
-                self.next_piece = Piece::l_piece();
+    }
+
+    fn handle_game_events(&mut self) {
+        for event in self.game.take_events() {
+            match event {
+                GameEvent::Scored(action) => self.last_action = Some((action, self.game.ticks())),
+                // an online opponent reports the garbage its game received itself
+                GameEvent::Attack(rows) if self.online.is_none() => {
+                    if let Some(opponent) = &mut self.opponent {
+                        opponent.game.receive_garbage(rows);
+                    }
+                }
+                GameEvent::Locked(_) => {
+                    if let Some(history) = &mut self.history {
+                        history.record(&self.game);
+                    }
+                }
+                GameEvent::GameOver(_) if self.playback.is_none() => {
+                    // a failed write shouldn't end the game, the replay is just lost
+                    let _ = self.save_replay();
+                    self.offer_entry();
+                }
+                _ => {}
             }
         }
-        else if random_num == 4 {
-            let random_num_for_orientation = rng.gen_range(0.0..1.0);
-            if random_num_for_orientation < 0.5 {
-                self.next_piece = Piece::inverted_z_piece();
-            }
-            else {
-                self.next_piece = Piece::z_piece();
+        // a spectator gets the garbage the host received from the host
+        let watching = self.is_watching();
+        if let Some(opponent) = &mut self.opponent {
+            for event in opponent.game.take_events() {
+                match event {
+                    GameEvent::Scored(action) => opponent.last_action = Some((action, opponent.game.ticks())),
+                    GameEvent::Attack(rows) if !watching && !self.game.is_over() && !opponent.game.is_over() => {
+                        self.game.receive_garbage(rows);
+                        if let Some(online) = &mut self.online {
+                            online.send(Message::Garbage { tick: self.game.ticks(), rows });
+                        }
+                    }
+                    _ => {}
+                }
             }
         }
-        self.next_piece.color = colors[rng.gen_range(0..colors.len())];
+        // the other modes keep their best runs in the personal bests
+        if self.playback.is_none() && self.game.mode() == GameMode::Marathon {
+            self.highscore();
+        }
+    }
 
-        for _ in 0..12 {
-            self.next_piece.move_right(true)?;
+    // Asks for a name if the finished run made the leaderboard.
+    fn offer_entry(&mut self) {
+        if !self.game.mode().is_ranked() {
+            return;
         }
-        for _ in 0..3 {
-            self.next_piece.move_down()?;
+        // runs ranked by time only count if they reached the goal
+        if self.game.mode().ranks_by_time() && self.game.game_over() != Some(GameOver::Finished) {
+            return;
+        }
+        let mut entry = Entry::from_game(&self.game);
+        if self.personal_bests.update(self.game.mode(), &entry) {
+            // like the replay, a failed write only loses this run
+            if let Ok(path) = personal_bests_path() {
+                let _ = self.personal_bests.save(&path);
+            }
+        }
+        if let Some(rank) = self.leaderboard.rank(self.game.mode(), &entry) {
+            entry.name = self.leaderboard.last_name.clone();
+            self.screen = Screen::NameEntry { entry, rank };
         }
-        Ok(())
     }
 
-    fn move_current_down(&mut self) -> Result<()> {
-        let mut current_piece = self.current_piece.clone();
-        current_piece.move_down()?;
-        if !current_piece.components.iter().map(|cmp| {
-            self.pieces.iter().map(|piece| {
-                piece.is_blocked(cmp)
-            }).any(|x| x)
-        }).any(|x| x) {
-            self.current_piece.move_down()?;
+    fn save_replay(&mut self) -> Result<()> {
+        self.recording.finish(self.game.ticks());
+        // undoing placements isn't an input, a practice game can't be played back,
+        // and the recording of a versus game misses the inputs of the second player
+        if self.recording.is_empty() || self.game.mode().is_practice() || self.opponent.is_some() {
+            return Ok(());
         }
-        Ok(())
+        save_replay(&replay_path()?, &self.recording).wrap_err("saving the replay failed")
     }
 
-    fn move_current_left(&mut self) -> Result<()> {
-        let mut current_piece = self.current_piece.clone();
-        current_piece.move_left(false)?;
-        if !(current_piece.components.iter().map(|cmp| {
-            self.pieces.iter().map(|piece| {
-                piece.is_blocked(cmp)
-            }).any(|x| x)
-        }).any(|x| x) || current_piece.out_of_bounds()) {
-            self.current_piece.move_left(false)?;
-        }
-        Ok(())
+    fn render_frame(&self, frame: &mut Frame) {
+        frame.render_widget(self, frame.size());
     }
 
-    fn move_current_right(&mut self) -> Result<()> {
-        let mut current_piece = self.current_piece.clone();
-        current_piece.move_right(false)?;
-        if !(current_piece.components.iter().map(|cmp| {
-            self.pieces.iter().map(|piece| {
-                piece.is_blocked(cmp)
-            }).any(|x| x)
-        }).any(|x| x) || current_piece.out_of_bounds()) {
-            self.current_piece.move_right(false)?;
+    fn highscore(&mut self) {
+        if self.game.score() > self.highscore {
+            self.highscore = self.game.score();
         }
-        Ok(())
     }
 
-    fn rotate_current(&mut self) -> Result<()> {
-        //TODO
-        let mut copy = self.current_piece.clone();
-        copy.rotate()?;
-        if !(copy.components.iter().map(|cmp| {
-            self.pieces.iter().map(|piece| {
-                piece.is_blocked(cmp)
-            }).any(|x| x)
-        }).any(|x| x) || copy.out_of_bounds()) {
-            self.current_piece.rotate()?;
+    fn handle_events(&mut self) -> Result<()> {
+        match event::read()? {
+            Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
+                self.handle_key_event(key_event).wrap_err_with(|| {
+                    format!("handling key event failed: \n{key_event:#?}")
+                })
+            }
+            Event::Key(key_event) if key_event.kind == KeyEventKind::Release => {
+                if let Some((player, action)) = self.key_action(key_event.code) {
+                    if let Some(input) = self.keys_of(player).release(action) {
+                        self.player_input(player, input);
+                    }
+                }
+                Ok(())
+            }
+           _ => Ok(())
         }
-        Ok(())
     }
-}
-
-#[derive(Debug, Default, Clone)]
-struct Piece {
-    color: Color,
-    components: Vec<SimplePiece>,
-    min_y: f64,    
-    max_y: f64,
-    center: Vec<f64>,
-}
-
-impl Piece {
 
-    fn move_right(&mut self, force: bool) -> Result<()> {
-        if self.components.clone().iter().any(|cmp|cmp.x >= 60.0) && !force {
-            return Ok(());
-        }
-        for piece in self.components.iter_mut() {
-            piece.x += 10.0;
-            piece.center[0] += 10.0;
+    pub fn new(settings: &Settings) -> Result<App> {
+        let config = settings.game_config();
+        let game = Game::new(&config);
+        let history = config.mode.is_practice().then(|| History::new(&game));
+        let opponent = config.mode.is_versus().then(|| Opponent::new(&config, settings.bot));
+        let mut app = App {
+            highscore: 0,
+            exit: false,
+            on_pause: false,
+            game,
+            settings: settings.clone(),
+            keys: KeyTracker::new(tui::has_release_events()),
+            last_action: None,
+            recording: Replay::new(config),
+            playback: None,
+            speed: NORMAL_SPEED,
+            leaderboard: Leaderboard::load(&leaderboard_path()?).wrap_err("reading the leaderboard failed")?,
+            history,
+            opponent,
+            online: None,
+            demo: settings.replay.is_none().then(Demo::new),
+            personal_bests: PersonalBests::load(&personal_bests_path()?).wrap_err("reading the personal bests failed")?,
+            screen: if settings.replay.is_none() { Screen::Title } else { Screen::Game },
+            padding: 0.0, // 0.1 seems good
+        };
+        if let Some(path) = &settings.replay {
+            // the last game is always saved, `--replay last` watches it
+            let path = if path.as_os_str() == "last" { replay_path()? } else { path.clone() };
+            let replay = read_replay(&path).wrap_err_with(|| format!("reading replay {} failed", path.display()))?;
+            let mut playback = Playback::new(replay);
+            app.game = playback.restart();
+            app.playback = Some(playback);
+            // replays only ever have a single player
+            app.opponent = None;
         }
-        self.set_center();
-        //self.center[0] -= 10.0;
-        Ok(())
+        Ok(app)
     }
 
-    fn move_left(&mut self, force: bool) -> Result<()> {
-        if self.components.clone().iter().any(|cmp|cmp.x <= -70.0) && !force {
+    fn handle_key_event(&mut self, key_event: KeyEvent) -> Result<()> {
+        match &self.screen {
+            Screen::Title => {
+                self.handle_title_key(key_event.code);
+                return Ok(());
+            }
+            Screen::NameEntry { .. } => return self.handle_name_key(key_event.code),
+            Screen::Leaderboard { .. } => {
+                self.handle_leaderboard_key(key_event.code);
+                return Ok(());
+            }
+            Screen::Game => {}
+        }
+        if key_event.code == KeyCode::Char('l') && self.online.is_none() {
+            if !self.on_pause && !self.is_over() {
+                self.pause()?;
+            }
+            let mode = Some(self.game.mode()).filter(|mode| mode.is_ranked()).unwrap_or_default();
+            self.screen = Screen::Leaderboard { mode, highlight: None };
             return Ok(());
         }
-        for piece in self.components.iter_mut() {
-            piece.x -= 10.0;
-            piece.center[0] -= 10.0;
+        if self.playback.is_some() {
+            self.handle_replay_key(key_event.code);
+            return Ok(());
+        }
+        match key_event.code {
+            KeyCode::Char('q') => self.exit(),
+            _ if self.is_watching() => {}
+            // the other player of an online game can't be paused
+            KeyCode::Esc if self.online.is_none() => self.pause()?,
+            KeyCode::Enter if self.online.is_some() => self.request_rematch(),
+            // the second player of a versus game hard drops with Enter
+            KeyCode::Enter if self.opponent.is_none() || self.is_over() => self.restart()?,
+            _ if self.on_pause || self.is_over() => {}
+            code if self.history.is_some() && self.handle_practice_key(code) => {}
+            code => {
+                if let Some((player, action)) = self.key_action(code) {
+                    for input in self.keys_of(player).press(action, Instant::now()) {
+                        self.player_input(player, input);
+                    }
+                    self.handle_game_events();
+                }
+            }
         }
-        //self.center[0] -= 10.0;
-        self.set_center();
         Ok(())
     }
 
-    fn move_down(&mut self) -> Result<()> {
-        if self.components.clone().iter().any(|cmp|cmp.y <= -90.0) {
-            return Ok(());
+    // Undo, redo, picking the next piece and clearing the board. Returns false for other keys.
+    fn handle_practice_key(&mut self, code: KeyCode) -> bool {
+        let Some(history) = &mut self.history else {
+            return false;
+        };
+        match code {
+            KeyCode::Char('u') => {
+                if let Some(game) = history.undo() {
+                    self.restore(game);
+                }
+            }
+            KeyCode::Char('r') => {
+                if let Some(game) = history.redo() {
+                    self.restore(game);
+                }
+            }
+            KeyCode::Char(c @ '1'..='7') => {
+                self.game.set_next_piece(PieceKind::ALL[c as usize - '1' as usize]);
+            }
+            KeyCode::Backspace => {
+                self.game.clear_board();
+                self.game.take_events();
+                history.record(&self.game);
+            }
+            _ => return false,
         }
-        for piece in self.components.iter_mut() {
-            piece.y -= 10.0;
-            piece.center[1] -= 10.0;
+        true
+    }
+
+    // Continues from a copy of the game taken by the history.
+    fn restore(&mut self, game: Game) {
+        self.game = game;
+        self.game.release_all();
+        self.keys.clear();
+        self.last_action = None;
+    }
+
+    // Enter starts the game, the demo stops for good then.
+    fn handle_title_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Char('q') => self.exit(),
+            KeyCode::Enter => {
+                self.demo = None;
+                self.screen = Screen::Game;
+            }
+            KeyCode::Char('l') => {
+                let mode = Some(self.settings.game.mode).filter(|mode| mode.is_ranked()).unwrap_or_default();
+                self.screen = Screen::Leaderboard { mode, highlight: None };
+            }
+            _ => {}
         }
-        self.min_y -= 10.0;
-        self.max_y -= 10.0;
-        //self.center[1] -= 10.0;
-        self.set_center();
-        Ok(())
     }
 
-    fn move_up(&mut self) -> Result<()> {
-        if self.components.clone().iter().any(|cmp|cmp.y >= 80.0) {
+    fn handle_name_key(&mut self, code: KeyCode) -> Result<()> {
+        let Screen::NameEntry { entry, .. } = &mut self.screen else {
             return Ok(());
+        };
+        match code {
+            KeyCode::Char(c) if !c.is_control() && entry.name.chars().count() < MAX_NAME_LEN => entry.name.push(c),
+            KeyCode::Backspace => {
+                entry.name.pop();
+            }
+            KeyCode::Esc => self.screen = Screen::Game,
+            KeyCode::Enter => {
+                let mut entry = entry.clone();
+                entry.name = entry.name.trim().to_string();
+                if entry.name.is_empty() {
+                    entry.name = "anonymous".to_string();
+                }
+                let mode = self.game.mode();
+                self.leaderboard.last_name = entry.name.clone();
+                let highlight = self.leaderboard.insert(mode, entry);
+                self.leaderboard.save(&leaderboard_path()?).wrap_err("saving the leaderboard failed")?;
+                self.screen = Screen::Leaderboard { mode, highlight };
+            }
+            _ => {}
         }
-        for piece in self.components.iter_mut() {
-            piece.y += 10.0;
-            piece.center[1] += 10.0;
-        }
-        self.min_y += 10.0;
-        self.max_y += 10.0;
-        //self.center[1] -= 10.0;
-        self.set_center();
         Ok(())
     }
 
-
-    fn rotate(&mut self) -> Result<()> {
-        //TODO
-        // In order to rotate the shape properly, it needs to be centered in the orign -> center, rotate, decenter
-        let angle: f64 = std::f64::consts::FRAC_PI_2;
-        //self.set_center();
-        for cmp in self.components.iter_mut() {
-            let x_shift = self.center[0];
-            let y_shift = self.center[1];
-            cmp.x -= x_shift;
-            cmp.y -= y_shift;
-            let x = cmp.x;
-            cmp.x = cmp.x * angle.cos() - cmp.y * angle.sin() + x_shift;
-            cmp.y = x * angle.sin() + cmp.y * angle.cos() + y_shift;
-        }
-        self.set_center();
-        self.min_y = get_min_y(self.components.clone()); 
-        self.max_y = get_max_y(self.components.clone());
-        let y_diff = round_to_tenths(self.min_y);
-        let x_diff = round_to_tenths(get_min_x(self.components.clone()));
-        self.min_y -= y_diff;
-        self.max_y -= y_diff;
-        for cmp in self.components.iter_mut() {
-            cmp.x -= x_diff;
-            cmp.y -= y_diff;
-            cmp.center[1] -= y_diff;
-            cmp.center[0] -= x_diff;
+    fn handle_leaderboard_key(&mut self, code: KeyCode) {
+        let Screen::Leaderboard { mode, highlight } = &mut self.screen else {
+            return;
+        };
+        let modes: Vec<GameMode> = GameMode::ALL.into_iter().filter(|mode| mode.is_ranked()).collect();
+        let index = modes.iter().position(|other| other == mode).unwrap_or(0);
+        match code {
+            KeyCode::Char('q') => self.exit(),
+            KeyCode::Esc | KeyCode::Enter | KeyCode::Char('l') => {
+                self.screen = if self.demo.is_some() { Screen::Title } else { Screen::Game };
+            }
+            KeyCode::Left => {
+                *mode = modes[(index + modes.len() - 1) % modes.len()];
+                *highlight = None;
+            }
+            KeyCode::Right => {
+                *mode = modes[(index + 1) % modes.len()];
+                *highlight = None;
+            }
+            _ => {}
         }
-        Ok(())
     }
 
-    fn is_blocked(&self, piece: &SimplePiece) -> bool {
-        self.components.iter().map(|cmp| {
-            cmp.is_equal(piece)
-        }).any(|x| x)
+    // Space pauses, . steps a single tick, the arrows seek, + and - change the speed.
+    fn handle_replay_key(&mut self, code: KeyCode) {
+        let Some(playback) = &mut self.playback else {
+            return;
+        };
+        match code {
+            KeyCode::Char('q') => self.exit(),
+            KeyCode::Esc | KeyCode::Char(' ') => self.on_pause = !self.on_pause,
+            KeyCode::Char('.') => {
+                self.on_pause = true;
+                playback.step(&mut self.game);
+            }
+            KeyCode::Left => {
+                let tick = self.game.ticks().saturating_sub(SEEK_TICKS);
+                self.last_action = None;
+                playback.seek(&mut self.game, tick);
+            }
+            KeyCode::Right => {
+                let tick = self.game.ticks() + SEEK_TICKS;
+                playback.seek(&mut self.game, tick);
+            }
+            KeyCode::Home | KeyCode::Enter => {
+                self.last_action = None;
+                self.game = playback.restart();
+            }
+            KeyCode::Char('+') | KeyCode::Up => self.speed = (self.speed + 1).min(REPLAY_SPEEDS.len() - 1),
+            KeyCode::Char('-') | KeyCode::Down => self.speed = self.speed.saturating_sub(1),
+            _ => {}
+        }
+        self.handle_game_events();
     }
 
-    fn out_of_bounds(&self) -> bool {
-        self.components.iter().map(|cmp| {
-            cmp.y < -90.0 || cmp.y > 80.0 || cmp.x < -70.0 || cmp.x > 60.0
-        }).any(|cmp| cmp)
+    pub fn go_online(&mut self, connection: Connection, spectators: Option<Spectators>, config: GameConfig) {
+        self.online = Some(Online {
+            connection,
+            spectators,
+            rematch: false,
+            remote_rematch: false,
+            sent_tick: 0,
+            ended: [false; 2],
+        });
+        self.start(config);
     }
 
-    fn long() -> Piece {
-        Piece {
-            color: Color::White,
-            components: vec![
-                SimplePiece::new(0.0, 90.0),
-                SimplePiece::new(0.0, 80.0),
-                SimplePiece::new(0.0, 70.0),
-                SimplePiece::new(0.0, 60.0)
-            ],
-            min_y: 60.0,
-            max_y: 90.0,
-            center: vec![0.0, 75.0],
+    // Starts a game of an online match, both players start it with the same config.
+    fn start(&mut self, config: GameConfig) {
+        if let Some(spectators) = self.online.as_mut().and_then(|online| online.spectators.as_mut()) {
+            spectators.start(&config);
         }
-    }
-
-    fn square() -> Piece {
-        Piece {
-            color: Color::White,
-            components: vec![
-                SimplePiece::new(0.0, 90.0),
-                SimplePiece::new(10.0, 90.0),
-                SimplePiece::new(0.0, 80.0),
-                SimplePiece::new(10.0, 80.0)
-            ],
-            min_y: 80.0,
-            max_y: 90.0,
-            center: vec![0.0, 85.0],
+        self.game = Game::new(&config);
+        self.opponent = Some(Opponent::new(&config, None));
+        self.demo = None;
+        self.recording = Replay::new(config);
+        self.keys.clear();
+        self.last_action = None;
+        self.on_pause = false;
+        self.screen = Screen::Game;
+        if let Some(online) = &mut self.online {
+            online.rematch = false;
+            online.remote_rematch = false;
+            online.sent_tick = 0;
+            online.ended = [false; 2];
         }
     }
 
-    fn t_piece() -> Piece {
-        Piece {
-            color: Color::White,
-            components: vec![
-                SimplePiece::new(0.0, 90.0),
-                SimplePiece::new(-10.0, 90.0),
-                SimplePiece::new(10.0, 90.0),
-                SimplePiece::new(0.0, 80.0)
-            ],
-            min_y: 80.0,
-            max_y: 90.0,
-            center: vec![0.0, 85.0],
+    // Applies what the other player sent to the copy of their game, a spectator
+    // applies the messages the host relays to the copies of both games.
+    fn receive(&mut self) {
+        let watching = self.is_watching();
+        let Some(online) = &mut self.online else {
+            return;
+        };
+        let host = online.connection.is_host();
+        for message in online.connection.receive() {
+            match message {
+                // the host picks the rules, a guest can't start games on it
+                Message::Start(_) if host => {}
+                Message::Start(config) => self.start(config),
+                Message::Rematch => {
+                    if let Some(online) = &mut self.online {
+                        online.remote_rematch = true;
+                    }
+                    self.try_rematch();
+                }
+                // only the host relays, and only to spectators, anyone else could move this game with it
+                Message::Relay { .. } if !watching => {}
+                Message::Relay { player, message } => {
+                    if let (Message::End(_), Some(online)) = (message.as_ref(), &mut self.online) {
+                        online.ended[(player != 0) as usize] = true;
+                    }
+                    if player == 0 {
+                        protocol::apply(&mut self.game, &message);
+                    }
+                    else if let Some(opponent) = &mut self.opponent {
+                        protocol::apply(&mut opponent.game, &message);
+                    }
+                }
+                message => {
+                    if let Some(online) = &mut self.online {
+                        if let Message::End(_) = message {
+                            online.ended[1] = true;
+                        }
+                        if let Some(spectators) = &mut online.spectators {
+                            spectators.relay(1, &message);
+                        }
+                    }
+                    if let Some(opponent) = &mut self.opponent {
+                        protocol::apply(&mut opponent.game, &message);
+                    }
+                }
+            }
         }
+        self.handle_game_events();
     }
 
-    fn l_piece() -> Piece {
-        Piece {
-            color: Color::White,
-            components: vec![
-                SimplePiece::new(0.0, 90.0),
-                SimplePiece::new(0.0, 80.0),
-                SimplePiece::new(0.0, 70.0),
-                SimplePiece::new(10.0, 70.0)
-            ],
-            min_y: 70.0,
-            max_y: 90.0,
-            center: vec![0.0, 80.0],
-        }
-    }
-    
-    fn inverted_l_piece() -> Piece {
-        Piece {
-            color: Color::White,
-            components: vec![
-                SimplePiece::new(0.0, 90.0),
-                SimplePiece::new(0.0, 80.0),
-                SimplePiece::new(0.0, 70.0),
-                SimplePiece::new(-10.0, 70.0)
-            ],
-            min_y: 70.0,
-            max_y: 90.0,
-            center: vec![0.0, 80.0],
+    // Tells the other player how far this game got and writes out everything queued,
+    // the host also takes in new spectators and sends them what is due.
+    fn send_progress(&mut self) {
+        let watching = self.is_watching();
+        let over = self.is_over();
+        let Some(online) = &mut self.online else {
+            return;
+        };
+        if !watching && online.sent_tick != self.game.ticks() {
+            online.sent_tick = self.game.ticks();
+            online.send(Message::Tick(online.sent_tick));
+        }
+        // this game won't change anymore, the other side can decide the result once it has it
+        if !watching && over && !online.ended[0] {
+            online.ended[0] = true;
+            online.send(Message::End(self.game.ticks()));
+        }
+        online.connection.flush();
+        if let Some(spectators) = &mut online.spectators {
+            spectators.update();
         }
     }
 
-    fn z_piece() -> Piece {
-        Piece {
-            color: Color::White,
-            components: vec![
-                SimplePiece::new(-10.0, 90.0),
-                SimplePiece::new(0.0, 90.0),
-                SimplePiece::new(0.0, 80.0),
-                SimplePiece::new(10.0, 80.0)
-            ],
-            min_y: 80.0,
-            max_y: 90.0,
-            center: vec![0.0, 90.0],
-        }
-    }
-    fn inverted_z_piece() -> Piece {
-        Piece {
-            color: Color::White,
-            components: vec![
-                SimplePiece::new(-10.0, 80.0),
-                SimplePiece::new(0.0, 80.0),
-                SimplePiece::new(0.0, 90.0),
-                SimplePiece::new(10.0, 90.0)
-            ],
-            min_y: 80.0,
-            max_y: 90.0,
-            center: vec![0.0, 90.0],
-        }
-    }
-
-    fn whole_line(y: f64) -> Piece {
-        Piece {
-            color: Color::White,
-            components: vec![
-                SimplePiece::new(-70.0, y),
-                SimplePiece::new(-60.0, y),
-                SimplePiece::new(-50.0, y),
-                SimplePiece::new(-40.0, y),
-                SimplePiece::new(-30.0, y),
-                SimplePiece::new(-20.0, y),
-                SimplePiece::new(-10.0, y),
-                SimplePiece::new(0.0, y),
-                SimplePiece::new(10.0, y),
-                SimplePiece::new(20.0, y),
-                SimplePiece::new(30.0, y),
-                SimplePiece::new(40.0, y),
-                SimplePiece::new(50.0, y),
-                SimplePiece::new(60.0, y),
-            ],
-            min_y: y,
-            max_y: y,
-            center: vec![5.0, y + 5.0],
+    // Asks for another game once this one is over.
+    fn request_rematch(&mut self) {
+        if !self.is_over() {
+            return;
+        }
+        let Some(online) = &mut self.online else {
+            return;
+        };
+        if online.connection.is_closed() || online.rematch {
+            return;
         }
+        online.rematch = true;
+        online.connection.send(&Message::Rematch);
+        self.try_rematch();
     }
 
-    fn placeholder() -> Piece {
-        Piece {
-            color: Color::White,
-            components: vec![],
-            min_y: 0.0,
-            max_y: 0.0,
-            center: vec![0.0, 0.0],
+    // The host starts the next game with a new seed once both players asked for it.
+    fn try_rematch(&mut self) {
+        let Some(online) = &mut self.online else {
+            return;
+        };
+        if !online.rematch || !online.remote_rematch || !online.connection.is_host() {
+            return;
         }
+        let config = self.settings.game_config();
+        online.connection.send(&Message::Start(config.clone()));
+        self.start(config);
     }
 
-    fn set_center(&mut self) {
-        self.center = get_center(self.components.clone());
-    }
-}
+    fn restart(&mut self) -> Result<()> {
 
-#[derive(Debug, Default, Clone)]
-struct SimplePiece {
-    x: f64,
-    y: f64,
-    width: f64,
-    height: f64,
-    center: Vec<f64>,
-}
+        if self.is_over() {
+            let path = highscore_path()?;
+            save(&path, self.highscore)?;
+            
+            let num = read(&path)?;
 
-impl SimplePiece {
-    
-    fn new(x: f64, y: f64) -> SimplePiece {
-        SimplePiece {
-            x,
-            y, 
-            width: 10.0,
-            height: 10.0,
-            center: vec![x + 5.0, y + 5.0],
+            self.highscore = num;
+            self.on_pause = false;
+            let config = self.settings.game_config();
+            self.game = Game::new(&config);
+            self.history = config.mode.is_practice().then(|| History::new(&self.game));
+            self.opponent = config.mode.is_versus().then(|| Opponent::new(&config, self.settings.bot));
+            self.recording = Replay::new(config);
+            self.keys.clear();
+            self.last_action = None;
         }
-    }
 
-    fn is_equal(&self, piece: &SimplePiece) -> bool {
-        self.x.to_i8() == piece.x.to_i8() && self.y.to_i8() == piece.y.to_i8()
+        Ok(())
     }
-}
 
-fn get_center(cmps: Vec<SimplePiece>) -> Vec<f64> {
-    vec![cmps.iter().map(|cmp| cmp.center[0]).sum::<f64>() / cmps.len().to_f64().unwrap(),
-        cmps.iter().map(|cmp| cmp.center[1]).sum::<f64>() / cmps.len().to_f64().unwrap()]
-}
+    fn exit(&mut self) {
+        self.exit = true;
+    }
 
-fn get_min_y(cmps: Vec<SimplePiece>) -> f64 {
-    let mut min = f64::INFINITY;
-    for cmp in cmps.iter() {
-        if cmp.y < min {
-            min = cmp.y;
+    fn pause(&mut self) -> Result<()> {
+        self.on_pause = !self.on_pause;
+        // released as inputs rather than with release_all so that the replay sees them too
+        let players = if self.opponent.is_some() { 2 } else { 1 };
+        for player in 0..players {
+            for action in [Action::Left, Action::Right, Action::SoftDrop] {
+                self.player_input(player, Input::Release(action));
+            }
+            self.keys_of(player).clear();
         }
+        Ok(())
     }
-    min
 }
 
-fn get_max_y(cmps: Vec<SimplePiece>) -> f64 {
-    let mut max = -f64::INFINITY;
-    for cmp in cmps.iter() {
-        if cmp.y > max {
-            max = cmp.y;
-        }
-    }
-    max
+// Game time as minutes, seconds and tenths.
+fn format_ticks(ticks: u64) -> String {
+    let tenths = ticks * 10 / TICKS_PER_SECOND as u64;
+    format!("{}:{:02}.{}", tenths / 600, tenths / 10 % 60, tenths % 10)
 }
 
-fn get_min_x(cmps: Vec<SimplePiece>) -> f64 {
-    let mut min = f64::INFINITY;
-    for cmp in cmps.iter() {
-        if cmp.x < min {
-            min = cmp.x;
-        }
+fn key_action(code: KeyCode) -> Option<Action> {
+    match code {
+        KeyCode::Left => Some(Action::Left),
+        KeyCode::Right => Some(Action::Right),
+        KeyCode::Down => Some(Action::SoftDrop),
+        KeyCode::Char(' ') => Some(Action::HardDrop),
+        KeyCode::Up | KeyCode::Char('x') => Some(Action::RotateClockwise),
+        KeyCode::Char('z') => Some(Action::RotateCounterClockwise),
+        KeyCode::Char('a') => Some(Action::Rotate180),
+        KeyCode::Char('c') => Some(Action::Hold),
+        _ => None,
     }
-    min
 }
 
-fn round_to_tenths(num: f64) -> f64 {
-    let int = num.round().to_i64().unwrap();
-    let diff = num / 10.0 - (int / 10).to_f64().unwrap();
-    diff * 10.0
-}
\ No newline at end of file
+// The first player on the left of the keyboard, the second on the arrows.
+fn versus_key_action(code: KeyCode) -> Option<(usize, Action)> {
+    match code {
+        KeyCode::Char('a') => Some((0, Action::Left)),
+        KeyCode::Char('d') => Some((0, Action::Right)),
+        KeyCode::Char('s') => Some((0, Action::SoftDrop)),
+        KeyCode::Char(' ') => Some((0, Action::HardDrop)),
+        KeyCode::Char('w') => Some((0, Action::RotateClockwise)),
+        KeyCode::Char('e') => Some((0, Action::RotateCounterClockwise)),
+        KeyCode::Char('r') => Some((0, Action::Rotate180)),
+        KeyCode::Char('f') => Some((0, Action::Hold)),
+        KeyCode::Left => Some((1, Action::Left)),
+        KeyCode::Right => Some((1, Action::Right)),
+        KeyCode::Down => Some((1, Action::SoftDrop)),
+        KeyCode::Enter => Some((1, Action::HardDrop)),
+        KeyCode::Up => Some((1, Action::RotateClockwise)),
+        KeyCode::Char('.') => Some((1, Action::RotateCounterClockwise)),
+        KeyCode::Char(',') => Some((1, Action::Rotate180)),
+        KeyCode::Char('/') => Some((1, Action::Hold)),
+        _ => None,
+    }
+}
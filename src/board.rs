@@ -0,0 +1,190 @@
+use crate::piece::PieceKind;
+
+pub const WIDTH: usize = 10;
+pub const VISIBLE_HEIGHT: usize = 20;
+// the rows above the visible field are a hidden buffer zone pieces can spawn and be pushed into
+pub const HEIGHT: usize = 2 * VISIBLE_HEIGHT;
+
+// What fills an occupied cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Cell {
+    Piece(PieceKind),
+    // rows pushed in from below the board
+    Garbage,
+}
+
+// A fixed grid of locked cells. (0, 0) is the bottom left corner, y grows upwards.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Board {
+    width: usize,
+    height: usize,
+    visible_height: usize,
+    cells: Vec<Option<Cell>>,
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Board::new(WIDTH, HEIGHT, VISIBLE_HEIGHT)
+    }
+}
+
+impl Board {
+
+    pub fn new(width: usize, height: usize, visible_height: usize) -> Board {
+        Board {
+            width,
+            height,
+            visible_height: visible_height.min(height),
+            cells: vec![None; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn visible_height(&self) -> usize {
+        self.visible_height
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return None;
+        }
+        Some(y as usize * self.width + x as usize)
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> Option<Cell> {
+        self.index(x, y).and_then(|i| self.cells[i])
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, cell: Option<Cell>) {
+        if let Some(i) = self.index(x, y) {
+            self.cells[i] = cell;
+        }
+    }
+
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        self.index(x, y).is_some()
+    }
+
+    pub fn is_free(&self, x: i32, y: i32) -> bool {
+        self.in_bounds(x, y) && self.get(x, y).is_none()
+    }
+
+    pub fn fits<I>(&self, cells: I) -> bool
+        where
+            I: IntoIterator<Item = (i32, i32)> {
+        cells.into_iter().all(|(x, y)| self.is_free(x, y))
+    }
+
+    pub fn lock<I>(&mut self, cells: I, kind: PieceKind)
+        where
+            I: IntoIterator<Item = (i32, i32)> {
+        for (x, y) in cells {
+            self.set(x, y, Some(Cell::Piece(kind)));
+        }
+    }
+
+    pub fn row(&self, y: usize) -> &[Option<Cell>] {
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+
+    pub fn row_is_full(&self, y: usize) -> bool {
+        self.row(y).iter().all(|cell| cell.is_some())
+    }
+
+    pub fn row_is_empty(&self, y: usize) -> bool {
+        self.row(y).iter().all(|cell| cell.is_none())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.iter().all(|cell| cell.is_none())
+    }
+
+    pub fn full_rows(&self) -> Vec<usize> {
+        (0..self.height).filter(|y| self.row_is_full(*y)).collect()
+    }
+
+    // Removes every full row, lets the rows above fall down and returns how many were removed.
+    pub fn clear_full_rows(&mut self) -> usize {
+        let width = self.width;
+        let mut kept: Vec<Option<Cell>> = Vec::with_capacity(self.cells.len());
+        for row in self.cells.chunks(width) {
+            if !row.iter().all(|cell| cell.is_some()) {
+                kept.extend_from_slice(row);
+            }
+        }
+        let cleared = self.height - kept.len() / width;
+        kept.resize(self.cells.len(), None);
+        self.cells = kept;
+        cleared
+    }
+
+    // Shifts every row up by one and puts `row` in at the bottom. Returns false if that
+    // pushed blocks out of the top of the board, those are lost.
+    pub fn push_row(&mut self, row: &[Option<Cell>]) -> bool {
+        let overflow = !self.row_is_empty(self.height - 1);
+        let mut row = row.to_vec();
+        row.resize(self.width, None);
+        self.cells.truncate(self.cells.len() - self.width);
+        self.cells.splice(0..0, row);
+        !overflow
+    }
+
+    // Pushes in a row of garbage with a single hole at `hole`.
+    pub fn push_garbage(&mut self, hole: usize) -> bool {
+        let row: Vec<Option<Cell>> = (0..self.width)
+            .map(|x| if x == hole { None } else { Some(Cell::Garbage) })
+            .collect();
+        self.push_row(&row)
+    }
+
+    // Iterates over all occupied cells as (x, y, cell).
+    pub fn occupied(&self) -> impl Iterator<Item = (i32, i32, Cell)> + '_ {
+        self.cells.iter().enumerate().filter_map(|(i, cell)| {
+            cell.map(|cell| ((i % self.width) as i32, (i / self.width) as i32, cell))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pushed_rows_report_blocks_pushed_out_of_the_top() {
+        let mut board = Board::new(4, 3, 2);
+        assert!(board.push_garbage(0));
+        assert!(board.push_garbage(1));
+        assert!(board.push_garbage(2));
+        assert_eq!(board.occupied().count(), 9);
+        assert!(!board.push_garbage(3));
+        // the first row went out of the top, the newest is at the bottom
+        assert_eq!(board.row(0), [Some(Cell::Garbage), Some(Cell::Garbage), Some(Cell::Garbage), None]);
+        assert_eq!(board.row(2)[1], None);
+        assert_eq!(board.occupied().count(), 9);
+
+        // a short row is filled up with empty cells
+        let mut board = Board::new(4, 3, 2);
+        assert!(board.push_row(&[Some(Cell::Garbage)]));
+        assert_eq!(board.row(0), [Some(Cell::Garbage), None, None, None]);
+    }
+
+    #[test]
+    fn full_rows_are_cleared_and_the_rest_falls() {
+        let mut board = Board::new(4, 4, 4);
+        board.push_garbage(1);
+        board.push_row(&[Some(Cell::Garbage); 4]);
+        board.set(2, 2, Some(Cell::Piece(PieceKind::T)));
+        assert_eq!(board.full_rows(), [0]);
+        assert_eq!(board.clear_full_rows(), 1);
+        assert_eq!(board.row(0)[1], None);
+        assert_eq!(board.get(2, 1), Some(Cell::Piece(PieceKind::T)));
+        assert_eq!(board.occupied().count(), 4);
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use rand::Rng;
+
+use crate::board::Board;
+use crate::game::{Game, TICKS_PER_SECOND};
+use crate::input::{Action, Input};
+use crate::randomizer::{seeded_rng, GameRng};
+
+// How much each feature of a board is worth, higher is better. Holes and height are bad,
+// uneven surfaces and deep wells make later pieces hard to place, cleared lines are good.
+const HEIGHT_WEIGHT: f64 = -0.51;
+const HOLES_WEIGHT: f64 = -0.36;
+const BUMPINESS_WEIGHT: f64 = -0.18;
+const WELLS_WEIGHT: f64 = -0.1;
+const LINES_WEIGHT: f64 = 0.76;
+// placements that end the game are only taken if nothing else is left
+const GAME_OVER_SCORE: f64 = -1e9;
+
+// How well the computer plays.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+        }
+    }
+
+    // Pieces placed per second.
+    pub fn pps(self) -> f64 {
+        match self {
+            Difficulty::Easy => 1.0,
+            Difficulty::Medium => 2.0,
+            Difficulty::Hard => 3.5,
+        }
+    }
+
+    // The most that is added to or taken from the score of a placement at random,
+    // so that weaker bots now and then pick a worse one.
+    pub fn noise(self) -> f64 {
+        match self {
+            Difficulty::Easy => 3.0,
+            Difficulty::Medium => 0.8,
+            Difficulty::Hard => 0.0,
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Difficulty::ALL.into_iter()
+            .find(|difficulty| difficulty.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<&str> = Difficulty::ALL.iter().map(|difficulty| difficulty.name()).collect();
+                format!("unknown difficulty {s:?}, expected one of {}", names.join(", "))
+            })
+    }
+}
+
+// A computer player. It plays a game through the same inputs as a person: every placement
+// it can reach by holding, rotating once and moving sideways is tried on a copy of the game,
+// and the one leaving the best board is played with a hard drop.
+// A new piece is moved into place right away, only the hard drop waits for the pace of the
+// difficulty. Should gravity lock the piece first it still lands where the bot wanted it.
+#[derive(Debug, Clone)]
+pub struct Bot {
+    difficulty: Difficulty,
+    rng: GameRng,
+    // pieces locked in the game when the current piece was moved into place
+    placed: Option<u32>,
+    // ticks and pieces of the game when the bot started playing, its pace counts from there
+    start: Option<(u64, u32)>,
+}
+
+impl Bot {
+
+    pub fn new(difficulty: Difficulty, seed: u64) -> Bot {
+        Bot {
+            difficulty,
+            rng: seeded_rng(seed),
+            placed: None,
+            start: None,
+        }
+    }
+
+    // The inputs for `game` before its next tick, empty while the bot waits to keep its pace.
+    pub fn inputs(&mut self, game: &Game) -> Vec<Input> {
+        if game.is_over() {
+            return vec![];
+        }
+        let mut inputs = vec![];
+        if self.placed != Some(game.pieces()) {
+            self.placed = Some(game.pieces());
+            inputs = self.best_placement(game);
+        }
+        // the n-th piece drops n pieces' worth of time after the start, however long planning took
+        let (start_ticks, start_pieces) = *self.start.get_or_insert((game.ticks(), game.pieces()));
+        let pieces = game.pieces().saturating_sub(start_pieces) + 1;
+        let due = (pieces as f64 * ticks_per_piece(self.difficulty)).round() as u64;
+        if game.ticks() - start_ticks >= due {
+            inputs.push(Input::Press(Action::HardDrop));
+        }
+        inputs
+    }
+
+    // The inputs that move the current piece to the best spot, without the hard drop.
+    fn best_placement(&mut self, game: &Game) -> Vec<Input> {
+        let width = game.board().width() as i32;
+        let holds: &[bool] = if game.hold_used() { &[false] } else { &[false, true] };
+        let rotations = [
+            None,
+            Some(Action::RotateClockwise),
+            Some(Action::RotateCounterClockwise),
+            Some(Action::Rotate180),
+        ];
+        let mut seen = HashSet::new();
+        let mut best: Option<(f64, Vec<Input>)> = None;
+        for &hold in holds {
+            for rotation in rotations {
+                for dx in -width..=width {
+                    let mut inputs = vec![];
+                    if hold {
+                        inputs.push(Input::Press(Action::Hold));
+                    }
+                    if let Some(rotation) = rotation {
+                        inputs.push(Input::Press(rotation));
+                    }
+                    let direction = if dx < 0 { Action::Left } else { Action::Right };
+                    for _ in 0..dx.abs() {
+                        inputs.push(Input::Press(direction));
+                        inputs.push(Input::Release(direction));
+                    }
+                    let mut copy = game.clone();
+                    for input in &inputs {
+                        copy.input(*input);
+                    }
+                    // moves into a wall end up where fewer moves already did
+                    let mut cells: Vec<(i32, i32)> = copy.ghost_piece().cells().collect();
+                    cells.sort();
+                    if !seen.insert((hold, cells)) {
+                        continue;
+                    }
+                    copy.input(Input::Press(Action::HardDrop));
+                    let score = self.score(game, &copy);
+                    if best.as_ref().is_none_or(|(best, _)| score > *best) {
+                        best = Some((score, inputs));
+                    }
+                }
+            }
+        }
+        best.map_or_else(Vec::new, |(_, inputs)| inputs)
+    }
+
+    // How good the game is after a placement, `before` is the game before it.
+    fn score(&mut self, before: &Game, after: &Game) -> f64 {
+        if after.is_over() {
+            return GAME_OVER_SCORE;
+        }
+        let lines = after.lines() - before.lines();
+        let noise = self.difficulty.noise();
+        let noise = if noise > 0.0 { self.rng.gen_range(-noise..noise) } else { 0.0 };
+        evaluate(after.board()) + LINES_WEIGHT * lines as f64 + noise
+    }
+}
+
+fn ticks_per_piece(difficulty: Difficulty) -> f64 {
+    TICKS_PER_SECOND as f64 / difficulty.pps()
+}
+
+// The heuristic value of a board without counting the lines that led to it.
+pub fn evaluate(board: &Board) -> f64 {
+    let heights: Vec<i32> = (0..board.width() as i32)
+        .map(|x| (0..board.height() as i32).rev().find(|&y| !board.is_free(x, y)).map_or(0, |y| y + 1))
+        .collect();
+    let height: i32 = heights.iter().sum();
+    let holes: i32 = heights.iter().enumerate()
+        .map(|(x, &top)| (0..top).filter(|&y| board.is_free(x as i32, y)).count() as i32)
+        .sum();
+    let bumpiness: i32 = heights.windows(2).map(|pair| (pair[0] - pair[1]).abs()).sum();
+    // a well is a column lower than both neighbours, the walls count as high as the other neighbour
+    let wells: i32 = (0..heights.len())
+        .map(|x| {
+            let left = if x > 0 { heights[x - 1] } else { i32::MAX };
+            let right = heights.get(x + 1).copied().unwrap_or(i32::MAX);
+            (left.min(right) - heights[x]).max(0)
+        })
+        .sum();
+    HEIGHT_WEIGHT * height as f64
+        + HOLES_WEIGHT * holes as f64
+        + BUMPINESS_WEIGHT * bumpiness as f64
+        + WELLS_WEIGHT * wells as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GameConfig;
+
+    #[test]
+    fn every_difficulty_survives_at_a_high_level() {
+        for difficulty in Difficulty::ALL {
+            let config = GameConfig { seed: 1, start_level: 15, ..GameConfig::default() };
+            let mut game = Game::new(&config);
+            let mut bot = Bot::new(difficulty, config.seed);
+            while !game.is_over() && game.pieces() < 50 {
+                for input in bot.inputs(&game) {
+                    game.input(input);
+                }
+                game.tick();
+            }
+            assert!(!game.is_over(), "{} topped out after {} pieces", difficulty.name(), game.pieces());
+        }
+    }
+
+    #[test]
+    fn every_difficulty_plays_at_its_pace() {
+        for difficulty in Difficulty::ALL {
+            let config = GameConfig { seed: 2, ..GameConfig::default() };
+            let mut game = Game::new(&config);
+            let mut bot = Bot::new(difficulty, config.seed);
+            let seconds = 30;
+            for _ in 0..seconds * TICKS_PER_SECOND {
+                for input in bot.inputs(&game) {
+                    game.input(input);
+                }
+                game.tick();
+            }
+            assert!(!game.is_over());
+            let pps = game.pieces() as f64 / seconds as f64;
+            assert!((pps - difficulty.pps()).abs() < 0.05, "{} played {pps} pieces per second", difficulty.name());
+        }
+    }
+
+    #[test]
+    fn difficulties_parse_by_name() {
+        for difficulty in Difficulty::ALL {
+            assert_eq!(difficulty.name().parse(), Ok(difficulty));
+        }
+        assert!("impossible".parse::<Difficulty>().is_err());
+    }
+}
@@ -0,0 +1,57 @@
+use crate::mode::GameMode;
+use crate::randomizer::RandomizerKind;
+
+// The rules a game is played with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameConfig {
+    // the same seed and the same inputs always play out the same game
+    pub seed: u64,
+    pub mode: GameMode,
+    pub randomizer: RandomizerKind,
+    // how many times faster than gravity a soft drop moves the piece
+    pub soft_drop_factor: u32,
+    pub lock_delay_ms: u32,
+    // moves and rotations on the ground that restart the lock delay before the piece locks anyway
+    pub lock_resets: u32,
+    // delayed auto shift: how long left or right has to be held before the piece starts repeating
+    pub das_ms: u32,
+    // auto repeat rate: time between two repeated moves, 0 moves all the way to the wall
+    pub arr_ms: u32,
+    pub start_level: u32,
+    pub lines_per_level: u32,
+    // number of upcoming pieces shown, 1 to 7
+    pub queue_size: usize,
+    // length of modes with a time limit
+    pub time_limit_ms: u32,
+    // rows of garbage dig mode starts with
+    pub garbage_rows: u32,
+    // time between two more rows of garbage in dig mode, 0 for none
+    pub garbage_interval_ms: u32,
+    // time garbage sent by the opponent of a versus game waits before it rises
+    pub garbage_delay_ms: u32,
+    // chance in percent that a row of an attack has its hole somewhere else than the row below
+    pub garbage_messiness: u32,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            seed: 0,
+            mode: GameMode::default(),
+            randomizer: RandomizerKind::default(),
+            soft_drop_factor: 20,
+            lock_delay_ms: 500,
+            lock_resets: 15,
+            das_ms: 167,
+            arr_ms: 33,
+            start_level: 1,
+            lines_per_level: 10,
+            queue_size: 5,
+            time_limit_ms: 120_000,
+            garbage_rows: 10,
+            garbage_interval_ms: 0,
+            garbage_delay_ms: 500,
+            garbage_messiness: 0,
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::io::{self, Read, Write};
+
+// The building blocks of replays, network messages and save files: little endian numbers
+// and strings of one length byte followed by at most 255 bytes of UTF-8.
+
+pub fn write_u32(writer: &mut impl Write, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub fn write_u64(writer: &mut impl Write, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+// Longer strings are cut off at the last whole character that fits.
+pub fn write_str(writer: &mut impl Write, value: &str) -> io::Result<()> {
+    let mut len = value.len().min(u8::MAX as usize);
+    while !value.is_char_boundary(len) {
+        len -= 1;
+    }
+    writer.write_all(&[len as u8])?;
+    writer.write_all(&value.as_bytes()[..len])
+}
+
+pub fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut buffer = [0u8; 4];
+    reader.read_exact(&mut buffer)?;
+    Ok(u32::from_le_bytes(buffer))
+}
+
+pub fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut buffer = [0u8; 8];
+    reader.read_exact(&mut buffer)?;
+    Ok(u64::from_le_bytes(buffer))
+}
+
+pub fn read_str(reader: &mut impl Read) -> io::Result<String> {
+    let mut len = [0u8; 1];
+    reader.read_exact(&mut len)?;
+    let mut buffer = vec![0u8; len[0] as usize];
+    reader.read_exact(&mut buffer)?;
+    String::from_utf8(buffer).map_err(|_| invalid("string is not valid UTF-8"))
+}
+
+pub fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
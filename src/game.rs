@@ -0,0 +1,939 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::board::Board;
+use crate::config::GameConfig;
+use crate::garbage::{self, GarbageQueue};
+use crate::input::{Action, AutoRepeat, HeldKey, Input};
+use crate::level::{Level, GRAVITY_UNIT, MAX_GRAVITY};
+use crate::mode::GameMode;
+use crate::piece::{Piece, PieceKind};
+use crate::randomizer::{seeded_rng, GameRng, Randomizer};
+use crate::scoring::{self, ScoreAction, ScoreStats, Scoring, TSpin};
+use crate::srs::{self, RotationDirection};
+
+// the game logic advances in fixed steps of TICK
+pub const TICKS_PER_SECOND: u32 = 60;
+pub const TICK: Duration = Duration::from_nanos(1_000_000_000 / TICKS_PER_SECOND as u64);
+pub const MAX_QUEUE_SIZE: usize = 7;
+
+// points per cell moved by soft and hard drops
+const SOFT_DROP_SCORE: u64 = 1;
+const HARD_DROP_SCORE: u64 = 2;
+
+pub fn ms_to_ticks(ms: u32) -> u32 {
+    // in u64 so that long durations from the command line can't overflow
+    let ticks = (ms as u64 * TICKS_PER_SECOND as u64 + 500) / 1000;
+    ticks.min(u32::MAX as u64) as u32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameOver {
+    // a new piece spawned overlapping the stack
+    BlockOut,
+    // a piece locked without any block inside the visible field
+    LockOut,
+    // garbage pushed blocks out of the top of the board
+    TopOut,
+    // the mode ended the game, all lines of a sprint cleared or the time of an ultra run out
+    Finished,
+}
+
+// What happened during an input or a tick, for front ends to react to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameEvent {
+    Spawned(PieceKind),
+    Held(PieceKind),
+    Locked(PieceKind),
+    Scored(ScoreAction),
+    LevelUp(u32),
+    // rows of garbage sent to the opponent in a versus game
+    Attack(u32),
+    GameOver(GameOver),
+}
+
+// A single player game. Inputs go in, events and state come out. The game knows
+// nothing about terminals, files or clocks, it only advances when `tick` is called.
+#[derive(Debug, Clone)]
+pub struct Game {
+    seed: u64,
+    mode: GameMode,
+    board: Board,
+    current_piece: Piece,
+    queue: VecDeque<PieceKind>,
+    queue_size: usize,
+    held_piece: Option<PieceKind>,
+    hold_used: bool,
+    randomizer: Box<dyn Randomizer>,
+    score: u64,
+    // pieces locked so far
+    pieces: u32,
+    level: Level,
+    scoring: Scoring,
+    score_stats: ScoreStats,
+    soft_drop_factor: u32,
+    auto_repeat: AutoRepeat,
+    // fraction of a cell the piece has fallen, in GRAVITY_UNIT
+    gravity_progress: u32,
+    lock_ticks: u32,
+    lock_delay: u32,
+    lock_resets: u32,
+    max_lock_resets: u32,
+    // lowest row the current piece has reached, moving below it gives back all lock resets
+    lowest_y: i32,
+    // direction and kick of the last rotation, None once the piece moved after it
+    last_kick: Option<(RotationDirection, usize)>,
+    ticks: u64,
+    // the game ends after this many ticks
+    time_limit: Option<u64>,
+    // picks the holes of garbage rows, apart from the randomizer so that the pieces stay the same
+    garbage_rng: GameRng,
+    // ticks between two rows of garbage in dig mode, 0 for none
+    garbage_interval: u32,
+    // rows of the starting garbage of dig mode that are still on the board
+    dig_rows: Vec<i32>,
+    // rows of garbage sent to the opponent so far
+    garbage_sent: u32,
+    // garbage received from the opponent that is not on the board yet
+    incoming_garbage: GarbageQueue,
+    // chance in percent that a row of garbage moves its hole
+    garbage_messiness: u32,
+    over: Option<GameOver>,
+    events: Vec<GameEvent>,
+}
+
+impl Game {
+
+    pub fn new(config: &GameConfig) -> Game {
+        let mut game = Game {
+            seed: config.seed,
+            mode: config.mode,
+            board: Board::default(),
+            current_piece: Piece::default(),
+            queue: VecDeque::new(),
+            queue_size: config.queue_size.clamp(1, MAX_QUEUE_SIZE),
+            held_piece: None,
+            hold_used: false,
+            randomizer: config.randomizer.build(config.seed),
+            score: 0,
+            pieces: 0,
+            level: Level::new(config.start_level, config.lines_per_level),
+            scoring: Scoring::new(),
+            score_stats: ScoreStats::default(),
+            soft_drop_factor: config.soft_drop_factor.max(1),
+            auto_repeat: AutoRepeat::new(ms_to_ticks(config.das_ms), ms_to_ticks(config.arr_ms)),
+            gravity_progress: 0,
+            lock_ticks: 0,
+            lock_delay: ms_to_ticks(config.lock_delay_ms).max(1),
+            lock_resets: 0,
+            max_lock_resets: config.lock_resets,
+            lowest_y: i32::MAX,
+            last_kick: None,
+            ticks: 0,
+            time_limit: config.mode.has_time_limit()
+                .then(|| config.time_limit_ms as u64 * TICKS_PER_SECOND as u64 / 1000),
+            garbage_rng: seeded_rng(config.seed),
+            garbage_interval: ms_to_ticks(config.garbage_interval_ms),
+            dig_rows: vec![],
+            garbage_sent: 0,
+            incoming_garbage: GarbageQueue::new(ms_to_ticks(config.garbage_delay_ms)),
+            garbage_messiness: config.garbage_messiness.min(100),
+            over: None,
+            events: vec![],
+        };
+        game.garbage_rng.set_stream(1);
+        if config.mode.starts_with_garbage() {
+            // leave room for the pieces to spawn
+            let rows = config.garbage_rows.clamp(1, game.board.visible_height() as u32 - 4);
+            for _ in 0..rows {
+                let hole = game.garbage_hole();
+                game.board.push_garbage(hole);
+            }
+            game.dig_rows = (0..rows as i32).collect();
+        }
+        for _ in 0..game.queue_size {
+            let kind = game.randomizer.next();
+            game.queue.push_back(kind);
+        }
+        game.next_piece();
+        game
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn mode(&self) -> GameMode {
+        self.mode
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn current_piece(&self) -> &Piece {
+        &self.current_piece
+    }
+
+    pub fn queue(&self) -> impl Iterator<Item = PieceKind> + '_ {
+        self.queue.iter().copied().take(self.queue_size)
+    }
+
+    pub fn held_piece(&self) -> Option<PieceKind> {
+        self.held_piece
+    }
+
+    pub fn hold_used(&self) -> bool {
+        self.hold_used
+    }
+
+    pub fn score(&self) -> u64 {
+        self.score
+    }
+
+    pub fn pieces(&self) -> u32 {
+        self.pieces
+    }
+
+    pub fn score_stats(&self) -> &ScoreStats {
+        &self.score_stats
+    }
+
+    pub fn level(&self) -> u32 {
+        self.level.level()
+    }
+
+    pub fn lines(&self) -> u32 {
+        self.level.lines()
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    pub fn game_over(&self) -> Option<GameOver> {
+        self.over
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.over.is_some()
+    }
+
+    // Lines still to clear to reach the goal of the mode, if it has one.
+    pub fn lines_left(&self) -> Option<u32> {
+        self.mode.line_goal().map(|goal| goal.saturating_sub(self.lines()))
+    }
+
+    // Milliseconds until the time limit of the mode runs out, if it has one.
+    pub fn time_left_ms(&self) -> Option<u64> {
+        self.time_limit.map(|limit| limit.saturating_sub(self.ticks) * 1000 / TICKS_PER_SECOND as u64)
+    }
+
+    // Rows of the starting garbage left to clear in dig mode.
+    pub fn garbage_left(&self) -> Option<u32> {
+        self.mode.starts_with_garbage().then_some(self.dig_rows.len() as u32)
+    }
+
+    pub fn garbage_sent(&self) -> u32 {
+        self.garbage_sent
+    }
+
+    // Garbage that rises from below once its delay is over and a piece locks without
+    // clearing lines.
+    pub fn incoming_garbage(&self) -> &GarbageQueue {
+        &self.incoming_garbage
+    }
+
+    // Queues rows of garbage sent by the opponent of a versus game.
+    pub fn receive_garbage(&mut self, rows: u32) {
+        if !self.mode.is_versus() || self.is_over() {
+            return;
+        }
+        self.incoming_garbage.receive(rows);
+    }
+
+    // Game time in milliseconds.
+    pub fn time_ms(&self) -> u64 {
+        self.ticks * 1000 / TICKS_PER_SECOND as u64
+    }
+
+    // Pieces per second, 0 before the first second of the game is over.
+    pub fn pps(&self) -> f64 {
+        if self.ticks < TICKS_PER_SECOND as u64 {
+            return 0.0;
+        }
+        self.pieces as f64 * TICKS_PER_SECOND as f64 / self.ticks as f64
+    }
+
+    // Hands out everything that happened since the last call.
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn input(&mut self, input: Input) {
+        if self.is_over() {
+            return;
+        }
+        match input {
+            Input::Press(action) => {
+                if let Some(key) = HeldKey::from_action(action) {
+                    self.auto_repeat.press(key);
+                }
+                match action {
+                    Action::Left => {
+                        self.try_move(-1, 0);
+                    }
+                    Action::Right => {
+                        self.try_move(1, 0);
+                    }
+                    Action::SoftDrop => self.soft_drop(),
+                    Action::HardDrop => self.hard_drop(),
+                    Action::RotateClockwise => {
+                        self.rotate_current(RotationDirection::Clockwise);
+                    }
+                    Action::RotateCounterClockwise => {
+                        self.rotate_current(RotationDirection::CounterClockwise);
+                    }
+                    Action::Rotate180 => {
+                        self.rotate_current(RotationDirection::Half);
+                    }
+                    Action::Hold => self.hold_current(),
+                }
+            }
+            Input::Release(action) => {
+                if let Some(key) = HeldKey::from_action(action) {
+                    self.auto_repeat.release(key);
+                }
+            }
+        }
+    }
+
+    // Replaces the next piece in practice mode.
+    pub fn set_next_piece(&mut self, kind: PieceKind) {
+        if !self.mode.is_practice() {
+            return;
+        }
+        if let Some(next) = self.queue.front_mut() {
+            *next = kind;
+        }
+    }
+
+    // Empties the board in practice mode, the current piece starts over at the top.
+    pub fn clear_board(&mut self) {
+        if !self.mode.is_practice() {
+            return;
+        }
+        self.board = Board::new(self.board.width(), self.board.height(), self.board.visible_height());
+        self.spawn_piece(self.current_piece.kind);
+    }
+
+    // Forgets all held keys, e.g. when the front end pauses and won't see the releases.
+    pub fn release_all(&mut self) {
+        self.auto_repeat.release_all();
+    }
+
+    // Advances the game by one fixed time step.
+    pub fn tick(&mut self) {
+        if self.is_over() {
+            return;
+        }
+        self.ticks += 1;
+        if let Some((key, cells)) = self.auto_repeat.tick() {
+            let dx = if key == HeldKey::Left { -1 } else { 1 };
+            for _ in 0..cells {
+                if !self.try_move(dx, 0) {
+                    break;
+                }
+            }
+        }
+        let practice = self.mode.is_practice();
+        let mut gravity = if practice { 0 } else { self.level.gravity() };
+        if self.auto_repeat.is_held(HeldKey::SoftDrop) {
+            gravity = self.level.gravity().saturating_mul(self.soft_drop_factor);
+        }
+        self.gravity_progress += gravity.min(MAX_GRAVITY);
+        while self.gravity_progress >= GRAVITY_UNIT {
+            self.gravity_progress -= GRAVITY_UNIT;
+            if !self.handle_piece() {
+                self.gravity_progress = 0;
+            }
+        }
+        // in practice pieces only lock on a hard drop
+        if !practice && self.current_piece_at_bottom() {
+            self.lock_ticks += 1;
+            if self.lock_ticks >= self.lock_delay {
+                self.lock_current();
+            }
+        }
+        else {
+            self.lock_ticks = 0;
+        }
+        self.incoming_garbage.tick();
+        if self.mode.starts_with_garbage() && self.garbage_interval > 0
+            && self.ticks.is_multiple_of(self.garbage_interval as u64) && !self.is_over() {
+            self.push_garbage();
+        }
+        if self.time_limit.is_some_and(|limit| self.ticks >= limit) && !self.is_over() {
+            self.end(GameOver::Finished);
+        }
+    }
+
+    // The current piece moved down as far as it can go.
+    pub fn ghost_piece(&self) -> Piece {
+        let mut ghost = self.current_piece.clone();
+        while self.board.fits(ghost.moved(0, -1).cells()) {
+            ghost.y -= 1;
+        }
+        ghost
+    }
+
+    fn end(&mut self, reason: GameOver) {
+        self.over = Some(reason);
+        self.events.push(GameEvent::GameOver(reason));
+    }
+
+    fn garbage_hole(&mut self) -> usize {
+        self.garbage_rng.gen_range(0..self.board.width() as u32) as usize
+    }
+
+    // Pushes in a row of garbage with a random hole from below.
+    fn push_garbage(&mut self) {
+        let hole = self.garbage_hole();
+        self.push_garbage_row(hole);
+    }
+
+    // Pushes in the garbage whose delay is over. Every attack gets a new hole, within one
+    // the hole moves with a chance of `garbage_messiness`. Runs between a lock and the
+    // next spawn, so there is no current piece to push up.
+    fn push_incoming_garbage(&mut self) {
+        for rows in self.incoming_garbage.take_ready() {
+            let mut hole = self.garbage_hole();
+            for row in 0..rows {
+                if row > 0 && self.garbage_rng.gen_range(0..100) < self.garbage_messiness {
+                    hole = self.garbage_hole();
+                }
+                if !self.board.push_garbage(hole) {
+                    self.end(GameOver::TopOut);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn push_garbage_row(&mut self, hole: usize) {
+        if !self.board.push_garbage(hole) {
+            self.end(GameOver::TopOut);
+            return;
+        }
+        for row in &mut self.dig_rows {
+            *row += 1;
+        }
+        // the current piece is pushed up along with the stack
+        if !self.board.fits(self.current_piece.cells()) {
+            self.current_piece.y += 1;
+            self.lowest_y += 1;
+            if !self.board.fits(self.current_piece.cells()) {
+                self.end(GameOver::BlockOut);
+            }
+        }
+    }
+
+    // Clears full rows and scores them, returns the number of rows cleared.
+    fn row_clear(&mut self, t_spin: TSpin) -> u32 {
+        let mut dug_out = false;
+        if !self.dig_rows.is_empty() {
+            let full = self.board.full_rows();
+            self.dig_rows.retain(|row| !full.contains(&(*row as usize)));
+            for row in &mut self.dig_rows {
+                *row -= full.iter().filter(|full_row| (**full_row as i32) < *row).count() as i32;
+            }
+            dug_out = self.dig_rows.is_empty();
+        }
+        let cleared = self.board.clear_full_rows() as u32;
+        let perfect_clear = cleared > 0 && self.board.is_empty();
+        if let Some(action) = self.scoring.lock(cleared, t_spin, perfect_clear, self.level.level()) {
+            self.score += action.points;
+            self.score_stats.add(&action);
+            // an attack cancels incoming garbage before the rest goes to the opponent
+            let attack = self.incoming_garbage.cancel(garbage::attack(&action));
+            if self.mode.is_versus() && attack > 0 {
+                self.garbage_sent += attack;
+                self.events.push(GameEvent::Attack(attack));
+            }
+            self.events.push(GameEvent::Scored(action));
+        }
+        if self.level.add_lines(cleared) {
+            self.events.push(GameEvent::LevelUp(self.level.level()));
+        }
+        if dug_out || self.mode.line_goal().is_some_and(|goal| self.level.lines() >= goal) {
+            self.end(GameOver::Finished);
+        }
+        cleared
+    }
+
+    // Lets the current piece fall by one cell, returns false if it is on the ground.
+    fn handle_piece(&mut self) -> bool {
+        let moved = self.try_move(0, -1);
+        if moved && self.auto_repeat.is_held(HeldKey::SoftDrop) {
+            self.score += SOFT_DROP_SCORE;
+        }
+        moved
+    }
+
+    fn lock_current(&mut self) {
+        let visible = self.board.visible_height() as i32;
+        let locked_out = self.current_piece.cells().all(|(_, y)| y >= visible);
+        let t_spin = scoring::detect_t_spin(&self.board, &self.current_piece, self.last_kick);
+        self.board.lock(self.current_piece.cells(), self.current_piece.kind);
+        self.hold_used = false;
+        self.pieces += 1;
+        self.events.push(GameEvent::Locked(self.current_piece.kind));
+        let cleared = self.row_clear(t_spin);
+        if self.is_over() {
+            return;
+        }
+        if locked_out && !self.mode.is_practice() {
+            self.end(GameOver::LockOut);
+            return;
+        }
+        if cleared == 0 {
+            self.push_incoming_garbage();
+            if self.is_over() {
+                return;
+            }
+        }
+        self.next_piece();
+    }
+
+    fn current_piece_at_bottom(&self) -> bool {
+        !self.board.fits(self.current_piece.moved(0, -1).cells())
+    }
+
+    fn next_piece(&mut self) {
+        let kind = self.queue.pop_front().unwrap_or_else(|| self.randomizer.next());
+        let next = self.randomizer.next();
+        self.queue.push_back(next);
+        self.spawn_piece(kind);
+    }
+
+    fn spawn_piece(&mut self, kind: PieceKind) {
+        let mut piece = Piece::new(kind);
+        piece.spawn(&self.board);
+        self.current_piece = piece;
+        self.gravity_progress = 0;
+        self.lock_ticks = 0;
+        self.lock_resets = 0;
+        self.lowest_y = self.current_piece.lowest_y();
+        self.events.push(GameEvent::Spawned(kind));
+        if !self.board.fits(self.current_piece.cells()) {
+            if !self.mode.is_practice() {
+                self.end(GameOver::BlockOut);
+                return;
+            }
+            self.board = Board::new(self.board.width(), self.board.height(), self.board.visible_height());
+        }
+        // pieces spawn above the visible field and drop into it right away
+        self.try_move(0, -1);
+    }
+
+    // Swaps the current piece with the held one, only once until the next piece locks.
+    fn hold_current(&mut self) {
+        if self.hold_used {
+            return;
+        }
+        let current = self.current_piece.kind;
+        self.events.push(GameEvent::Held(current));
+        match self.held_piece.replace(current) {
+            Some(held) => self.spawn_piece(held),
+            None => self.next_piece(),
+        }
+        self.hold_used = true;
+    }
+
+    // Moves down one cell right away, while the key is held gravity runs at the soft drop speed.
+    fn soft_drop(&mut self) {
+        self.gravity_progress = 0;
+        if self.try_move(0, -1) {
+            self.score += SOFT_DROP_SCORE;
+        }
+    }
+
+    fn hard_drop(&mut self) {
+        let ghost = self.ghost_piece();
+        self.score += HARD_DROP_SCORE * (self.current_piece.y - ghost.y) as u64;
+        // like a soft drop, falling after a rotation means the piece wasn't spun into place
+        if ghost.y != self.current_piece.y {
+            self.last_kick = None;
+        }
+        self.current_piece = ghost;
+        self.lock_current();
+    }
+
+    fn try_move(&mut self, dx: i32, dy: i32) -> bool {
+        let moved = self.current_piece.moved(dx, dy);
+        if self.board.fits(moved.cells()) {
+            self.current_piece = moved;
+            self.piece_moved();
+            self.last_kick = None;
+            return true;
+        }
+        false
+    }
+
+    // Restarts the lock delay if the piece was on the ground, up to the reset limit.
+    fn piece_moved(&mut self) {
+        let lowest_y = self.current_piece.lowest_y();
+        if lowest_y < self.lowest_y {
+            self.lowest_y = lowest_y;
+            self.lock_resets = 0;
+        }
+        if self.lock_ticks > 0 {
+            if self.lock_resets < self.max_lock_resets {
+                self.lock_ticks = 0;
+                self.lock_resets += 1;
+            }
+            else {
+                // out of resets, lock as soon as it is on the ground again
+                self.lock_ticks = self.lock_delay;
+            }
+        }
+    }
+
+    pub fn rotate_current(&mut self, direction: RotationDirection) -> bool {
+        if self.is_over() {
+            return false;
+        }
+        if let Some((rotated, kick)) = srs::try_rotate(&self.board, &self.current_piece, direction) {
+            self.current_piece = rotated;
+            self.piece_moved();
+            self.last_kick = Some((direction, kick));
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Cell;
+    use crate::srs::Rotation;
+
+    // Every kind of input, spreading the pieces over the board. One input every few ticks.
+    fn script() -> Vec<(u64, Input)> {
+        let mut inputs = vec![];
+        for piece in 0..80 {
+            if piece % 7 == 3 {
+                inputs.push(Input::Press(Action::Hold));
+            }
+            let rotation = [Action::RotateClockwise, Action::Rotate180, Action::RotateCounterClockwise][piece % 3];
+            inputs.push(Input::Press(rotation));
+            let direction = if piece % 2 == 0 { Action::Left } else { Action::Right };
+            for _ in 0..(piece / 2) % 6 {
+                inputs.push(Input::Press(direction));
+                inputs.push(Input::Release(direction));
+            }
+            if piece % 5 == 0 {
+                inputs.push(Input::Press(Action::SoftDrop));
+                inputs.push(Input::Release(Action::SoftDrop));
+            }
+            inputs.push(Input::Press(Action::HardDrop));
+        }
+        inputs.into_iter().enumerate().map(|(i, input)| (i as u64 * 3, input)).collect()
+    }
+
+    // Plays the script and returns the game with the order the pieces spawned in.
+    fn play(config: &GameConfig, script: &[(u64, Input)]) -> (Game, Vec<PieceKind>) {
+        let mut game = Game::new(config);
+        let mut spawned = vec![game.current_piece().kind];
+        let mut script = script.iter().peekable();
+        while !game.is_over() && script.peek().is_some() {
+            while let Some((_, input)) = script.next_if(|(tick, _)| *tick == game.ticks()) {
+                game.input(*input);
+            }
+            game.tick();
+            for event in game.take_events() {
+                if let GameEvent::Spawned(kind) = event {
+                    spawned.push(kind);
+                }
+            }
+        }
+        (game, spawned)
+    }
+
+    #[test]
+    fn same_seed_and_inputs_play_the_same_game() {
+        let config = GameConfig { seed: 7, ..GameConfig::default() };
+        let (one, one_spawned) = play(&config, &script());
+        let (two, two_spawned) = play(&config, &script());
+        assert!(one.pieces() > 20);
+        assert_eq!(one.board(), two.board());
+        assert_eq!(one.score(), two.score());
+        assert_eq!(one.lines(), two.lines());
+        assert_eq!(one.ticks(), two.ticks());
+        assert_eq!(one.game_over(), two.game_over());
+        assert_eq!(one_spawned, two_spawned);
+
+        let other = GameConfig { seed: 8, ..config };
+        let (_, other_spawned) = play(&other, &script());
+        assert_ne!(one_spawned, other_spawned);
+    }
+
+    // Upgrading rand or rand_chacha must not change the games of existing seeds,
+    // replays and seeds shared between players depend on them.
+    #[test]
+    fn seed_deals_the_same_pieces() {
+        use PieceKind::*;
+        let config = GameConfig { seed: 42, ..GameConfig::default() };
+        let mut game = Game::new(&config);
+        let mut pieces = vec![game.current_piece().kind];
+        pieces.extend(game.queue());
+        for _ in 0..8 {
+            game.input(Input::Press(Action::HardDrop));
+            pieces.extend(game.queue().last());
+        }
+        assert_eq!(pieces, [O, Z, T, L, J, S, I, O, Z, J, T, L, S, I]);
+    }
+
+    #[test]
+    fn long_durations_do_not_overflow() {
+        assert_eq!(ms_to_ticks(500), 30);
+        assert_eq!(ms_to_ticks(100_000_000), 6_000_000);
+        assert_eq!(ms_to_ticks(u32::MAX), 257_698_038);
+    }
+
+    // A game with a T at height `y` above a slot for a T pointing right in the bottom left corner.
+    fn t_slot(y: i32) -> Game {
+        let mut game = Game::new(&GameConfig::default());
+        for (x, y) in [(0, 0), (2, 0), (0, 2)] {
+            game.board.set(x, y, Some(Cell::Garbage));
+        }
+        game.current_piece = Piece { kind: PieceKind::T, rotation: Rotation::Spawn, x: 0, y };
+        game.take_events();
+        game
+    }
+
+    fn t_spins(game: &mut Game) -> Vec<TSpin> {
+        game.take_events().into_iter()
+            .filter_map(|event| match event {
+                GameEvent::Scored(action) => Some(action.t_spin),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn hard_drops_after_a_rotation_in_the_air_are_no_t_spins() {
+        let mut spun = t_slot(0);
+        assert!(spun.rotate_current(RotationDirection::Clockwise));
+        spun.input(Input::Press(Action::HardDrop));
+        assert_eq!(t_spins(&mut spun), [TSpin::Mini]);
+
+        let mut dropped = t_slot(6);
+        assert!(dropped.rotate_current(RotationDirection::Clockwise));
+        dropped.input(Input::Press(Action::HardDrop));
+        assert_eq!(dropped.board(), spun.board());
+        assert_eq!(t_spins(&mut dropped), []);
+    }
+
+    fn versus(messiness: u32) -> Game {
+        let config = GameConfig { seed: 4, mode: GameMode::Versus, garbage_delay_ms: 100, garbage_messiness: messiness, ..GameConfig::default() };
+        Game::new(&config)
+    }
+
+    // Column of the hole of each garbage row from the bottom.
+    fn holes(game: &Game) -> Vec<usize> {
+        (0..game.board.height())
+            .map(|y| game.board.row(y))
+            .take_while(|row| row.contains(&Some(Cell::Garbage)))
+            .map(|row| row.iter().position(|cell| cell.is_none()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn garbage_rises_only_after_a_lock_that_clears_nothing() {
+        let mut game = versus(0);
+        game.receive_garbage(3);
+        // still on its way in
+        game.input(Input::Press(Action::HardDrop));
+        assert_eq!(holes(&game), []);
+        for _ in 0..ms_to_ticks(100) {
+            game.tick();
+        }
+        // a line clear holds it back, one that is no perfect clear and so cancels nothing
+        game.board = Board::default();
+        for x in 4..10 {
+            game.board.set(x, 0, Some(Cell::Garbage));
+        }
+        game.board.set(9, 1, Some(Cell::Piece(PieceKind::O)));
+        game.current_piece = Piece { kind: PieceKind::I, rotation: Rotation::Spawn, x: 0, y: 10 };
+        game.input(Input::Press(Action::HardDrop));
+        assert_eq!(game.lines(), 1);
+        assert_eq!(holes(&game), []);
+        assert_eq!(game.incoming_garbage().rows(), 3);
+
+        game.input(Input::Press(Action::HardDrop));
+        assert_eq!(holes(&game).len(), 3);
+        assert_eq!(game.incoming_garbage().rows(), 0);
+    }
+
+    #[test]
+    fn messiness_moves_the_hole_within_an_attack() {
+        for (messiness, same_column) in [(0, true), (100, false)] {
+            let mut game = versus(messiness);
+            game.receive_garbage(8);
+            game.receive_garbage(1);
+            for _ in 0..ms_to_ticks(100) {
+                game.tick();
+            }
+            game.input(Input::Press(Action::HardDrop));
+            let holes = holes(&game);
+            assert_eq!(holes.len(), 9);
+            // the newest attack is pushed in last, at the bottom
+            let attack = &holes[1..];
+            assert_eq!(attack.iter().all(|hole| *hole == attack[0]), same_column, "{holes:?}");
+        }
+    }
+
+    // Puts `piece` in play like a freshly spawned one.
+    fn place(game: &mut Game, piece: Piece) {
+        game.lowest_y = piece.lowest_y();
+        game.current_piece = piece;
+        game.lock_ticks = 0;
+        game.lock_resets = 0;
+    }
+
+    fn tap(game: &mut Game, action: Action) {
+        game.input(Input::Press(action));
+        game.input(Input::Release(action));
+    }
+
+    fn ticks(game: &mut Game, ticks: u32) {
+        for _ in 0..ticks {
+            game.tick();
+        }
+    }
+
+    fn o(x: i32, y: i32) -> Piece {
+        Piece { kind: PieceKind::O, rotation: Rotation::Spawn, x, y }
+    }
+
+    #[test]
+    fn pieces_lock_after_the_lock_delay_on_the_ground() {
+        let mut game = Game::new(&GameConfig::default());
+        let delay = ms_to_ticks(500);
+        place(&mut game, o(4, 0));
+        ticks(&mut game, delay - 1);
+        assert_eq!(game.pieces(), 0);
+        game.tick();
+        assert_eq!(game.pieces(), 1);
+
+        // moving on the ground starts the delay over
+        place(&mut game, o(4, 2));
+        ticks(&mut game, delay - 5);
+        tap(&mut game, Action::Left);
+        ticks(&mut game, delay - 1);
+        assert_eq!(game.pieces(), 1);
+        game.tick();
+        assert_eq!(game.pieces(), 2);
+    }
+
+    #[test]
+    fn lock_resets_are_capped_until_the_piece_gets_lower() {
+        let config = GameConfig { lock_resets: 2, ..GameConfig::default() };
+        let mut game = Game::new(&config);
+        // an O on a ledge just as wide as it is
+        game.board.set(4, 4, Some(Cell::Garbage));
+        game.board.set(5, 4, Some(Cell::Garbage));
+        place(&mut game, o(4, 5));
+        ticks(&mut game, 10);
+        tap(&mut game, Action::Left);
+        ticks(&mut game, 10);
+        // off the ledge, the second and last reset
+        tap(&mut game, Action::Left);
+        assert_eq!(game.lock_resets, 2);
+        // reaching a lower row than ever gives all of them back
+        tap(&mut game, Action::SoftDrop);
+        assert_eq!(game.lock_resets, 0);
+        while !game.current_piece_at_bottom() {
+            game.tick();
+        }
+        assert_eq!(game.current_piece.y, 0);
+        for action in [Action::Left, Action::Right] {
+            ticks(&mut game, 20);
+            tap(&mut game, action);
+        }
+        ticks(&mut game, 20);
+        assert_eq!(game.pieces(), 0);
+        // out of resets a move doesn't help anymore
+        tap(&mut game, Action::Left);
+        game.tick();
+        assert_eq!(game.pieces(), 1);
+    }
+
+    #[test]
+    fn spawning_into_the_stack_is_a_block_out() {
+        let mut game = Game::new(&GameConfig::default());
+        place(&mut game, o(0, 0));
+        let visible = game.board.visible_height() as i32;
+        for x in 3..7 {
+            for y in visible - 2..visible + 3 {
+                game.board.set(x, y, Some(Cell::Garbage));
+            }
+        }
+        game.input(Input::Press(Action::HardDrop));
+        assert_eq!(game.game_over(), Some(GameOver::BlockOut));
+    }
+
+    #[test]
+    fn locking_above_the_visible_field_is_a_lock_out() {
+        let mut game = Game::new(&GameConfig::default());
+        let visible = game.board.visible_height() as i32;
+        for y in 0..visible {
+            game.board.set(0, y, Some(Cell::Garbage));
+        }
+        place(&mut game, o(0, visible + 2));
+        game.input(Input::Press(Action::HardDrop));
+        assert_eq!(game.game_over(), Some(GameOver::LockOut));
+
+        // a single block inside the field is enough to go on
+        let mut game = Game::new(&GameConfig::default());
+        for y in 0..visible - 1 {
+            game.board.set(0, y, Some(Cell::Garbage));
+        }
+        place(&mut game, o(0, visible + 2));
+        game.input(Input::Press(Action::HardDrop));
+        assert_eq!(game.game_over(), None);
+    }
+
+    // Fills the hole of the bottom row, the next lock clears it.
+    fn fill_bottom_hole(game: &mut Game) {
+        let hole = game.board.row(0).iter().position(|cell| cell.is_none()).unwrap();
+        game.board.set(hole as i32, 0, Some(Cell::Garbage));
+    }
+
+    #[test]
+    fn dig_ends_once_the_starting_garbage_is_cleared() {
+        let config = GameConfig { mode: GameMode::Dig, garbage_rows: 1, garbage_interval_ms: 100, ..GameConfig::default() };
+        let mut game = Game::new(&config);
+        assert_eq!(game.garbage_left(), Some(1));
+        ticks(&mut game, ms_to_ticks(100));
+        assert_eq!(holes(&game).len(), 2);
+
+        // rows the timer added don't count
+        fill_bottom_hole(&mut game);
+        place(&mut game, o(0, 10));
+        game.input(Input::Press(Action::HardDrop));
+        assert_eq!(game.lines(), 1);
+        assert_eq!(game.garbage_left(), Some(1));
+        assert_eq!(game.game_over(), None);
+
+        fill_bottom_hole(&mut game);
+        place(&mut game, o(0, 10));
+        game.input(Input::Press(Action::HardDrop));
+        assert_eq!(game.garbage_left(), Some(0));
+        assert_eq!(game.game_over(), Some(GameOver::Finished));
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::VecDeque;
+
+use crate::scoring::{ScoreAction, TSpin};
+
+// extra rows for consecutive line clears, indexed by the combo and capped at the last entry
+const COMBO_BONUS: [u32; 12] = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+const BACK_TO_BACK_BONUS: u32 = 1;
+const PERFECT_CLEAR_BONUS: u32 = 10;
+
+// Rows of garbage a scoring action sends to the opponent, following the guideline:
+// a double sends 1, a triple 2, a tetris 4, a T-spin twice the lines it cleared and
+// a T-spin mini double 1. Combos, back-to-backs and perfect clears add to that.
+pub fn attack(action: &ScoreAction) -> u32 {
+    if action.lines == 0 {
+        return 0;
+    }
+    let base = match (action.t_spin, action.lines) {
+        (TSpin::Full, lines) => 2 * lines,
+        (TSpin::Mini, lines) => lines - 1,
+        (TSpin::None, 4..) => 4,
+        (TSpin::None, lines) => lines - 1,
+    };
+    let combo = COMBO_BONUS[(action.combo as usize).min(COMBO_BONUS.len() - 1)];
+    let back_to_back = if action.back_to_back { BACK_TO_BACK_BONUS } else { 0 };
+    let perfect_clear = if action.perfect_clear { PERFECT_CLEAR_BONUS } else { 0 };
+    base + combo + back_to_back + perfect_clear
+}
+
+// One attack of the opponent on its way in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IncomingGarbage {
+    pub rows: u32,
+    // ticks until the rows can rise
+    pub delay: u32,
+}
+
+impl IncomingGarbage {
+
+    pub fn is_ready(&self) -> bool {
+        self.delay == 0
+    }
+}
+
+// Garbage received from the opponent that is not on the board yet, oldest first.
+// Attacks wait for a delay before they rise, until then line clears can still cancel them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GarbageQueue {
+    incoming: VecDeque<IncomingGarbage>,
+    // ticks an attack waits before it can rise
+    delay: u32,
+}
+
+impl GarbageQueue {
+
+    pub fn new(delay: u32) -> GarbageQueue {
+        GarbageQueue {
+            incoming: VecDeque::new(),
+            delay,
+        }
+    }
+
+    pub fn receive(&mut self, rows: u32) {
+        if rows > 0 {
+            self.incoming.push_back(IncomingGarbage { rows, delay: self.delay });
+        }
+    }
+
+    pub fn tick(&mut self) {
+        for garbage in &mut self.incoming {
+            garbage.delay = garbage.delay.saturating_sub(1);
+        }
+    }
+
+    // Uses an attack to cancel incoming garbage, oldest first. Returns what is left of
+    // the attack to send on to the opponent.
+    pub fn cancel(&mut self, mut attack: u32) -> u32 {
+        while attack > 0 {
+            let Some(garbage) = self.incoming.front_mut() else {
+                break;
+            };
+            let cancelled = garbage.rows.min(attack);
+            garbage.rows -= cancelled;
+            attack -= cancelled;
+            if garbage.rows == 0 {
+                self.incoming.pop_front();
+            }
+        }
+        attack
+    }
+
+    // Removes the attacks whose delay is over and returns their rows.
+    pub fn take_ready(&mut self) -> Vec<u32> {
+        let mut ready = vec![];
+        while let Some(garbage) = self.incoming.front() {
+            if !garbage.is_ready() {
+                break;
+            }
+            ready.push(garbage.rows);
+            self.incoming.pop_front();
+        }
+        ready
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &IncomingGarbage> + '_ {
+        self.incoming.iter()
+    }
+
+    // All incoming rows, ready or not.
+    pub fn rows(&self) -> u32 {
+        self.incoming.iter().map(|garbage| garbage.rows).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn action(lines: u32, t_spin: TSpin) -> ScoreAction {
+        ScoreAction { lines, t_spin, combo: 0, back_to_back: false, perfect_clear: false, points: 0 }
+    }
+
+    #[test]
+    fn attacks_follow_the_guideline_table() {
+        let table = [
+            (0, TSpin::None, 0),
+            (1, TSpin::None, 0),
+            (2, TSpin::None, 1),
+            (3, TSpin::None, 2),
+            (4, TSpin::None, 4),
+            (0, TSpin::Full, 0),
+            (1, TSpin::Full, 2),
+            (2, TSpin::Full, 4),
+            (3, TSpin::Full, 6),
+            (1, TSpin::Mini, 0),
+            (2, TSpin::Mini, 1),
+        ];
+        for (lines, t_spin, rows) in table {
+            assert_eq!(attack(&action(lines, t_spin)), rows, "{lines} lines, {t_spin:?}");
+        }
+        let bonus = ScoreAction { combo: 20, back_to_back: true, ..action(4, TSpin::None) };
+        assert_eq!(attack(&bonus), 4 + 5 + BACK_TO_BACK_BONUS);
+        let perfect = ScoreAction { combo: 2, perfect_clear: true, ..action(1, TSpin::None) };
+        assert_eq!(attack(&perfect), 1 + PERFECT_CLEAR_BONUS);
+    }
+
+    #[test]
+    fn attacks_cancel_the_oldest_garbage_first() {
+        let mut queue = GarbageQueue::new(10);
+        queue.receive(2);
+        queue.receive(0);
+        queue.receive(3);
+        assert_eq!(queue.iter().count(), 2);
+        assert_eq!(queue.cancel(3), 0);
+        assert_eq!(queue.iter().copied().collect::<Vec<_>>(), [IncomingGarbage { rows: 2, delay: 10 }]);
+        assert_eq!(queue.cancel(5), 3);
+        assert_eq!(queue.rows(), 0);
+    }
+
+    #[test]
+    fn garbage_is_ready_after_its_delay() {
+        let mut queue = GarbageQueue::new(2);
+        queue.receive(1);
+        queue.tick();
+        queue.receive(4);
+        assert_eq!(queue.take_ready(), Vec::<u32>::new());
+        queue.tick();
+        assert_eq!(queue.take_ready(), [1]);
+        queue.tick();
+        assert_eq!(queue.take_ready(), [4]);
+        assert_eq!(queue.rows(), 0);
+    }
+}
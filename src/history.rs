@@ -0,0 +1,126 @@
+use std::mem;
+
+use crate::game::Game;
+
+// Copies of a game after every placement, to undo and redo them in practice mode.
+#[derive(Debug, Clone)]
+pub struct History {
+    undo: Vec<Game>,
+    redo: Vec<Game>,
+    // the game as it was after the last placement
+    current: Game,
+}
+
+impl History {
+
+    pub fn new(game: &Game) -> History {
+        History {
+            undo: vec![],
+            redo: vec![],
+            current: game.clone(),
+        }
+    }
+
+    // Remembers the game after a placement, anything that was undone is gone.
+    pub fn record(&mut self, game: &Game) {
+        self.undo.push(mem::replace(&mut self.current, game.clone()));
+        self.redo.clear();
+    }
+
+    // The game before the last placement, None if there is nothing to undo.
+    pub fn undo(&mut self) -> Option<Game> {
+        let previous = self.undo.pop()?;
+        self.redo.push(mem::replace(&mut self.current, previous.clone()));
+        Some(previous)
+    }
+
+    pub fn redo(&mut self) -> Option<Game> {
+        let next = self.redo.pop()?;
+        self.undo.push(mem::replace(&mut self.current, next.clone()));
+        Some(next)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GameConfig;
+    use crate::input::{Action, Input};
+    use crate::mode::GameMode;
+    use crate::piece::PieceKind;
+
+    fn zen() -> Game {
+        Game::new(&GameConfig { seed: 6, mode: GameMode::Zen, ..GameConfig::default() })
+    }
+
+    fn drop_piece(game: &mut Game, history: &mut History) {
+        game.input(Input::Press(Action::HardDrop));
+        history.record(game);
+    }
+
+    #[test]
+    fn placements_are_undone_and_redone() {
+        let mut game = zen();
+        let mut history = History::new(&game);
+        assert!(!history.can_undo());
+        let start = game.board().clone();
+        drop_piece(&mut game, &mut history);
+        let one = game.board().clone();
+        drop_piece(&mut game, &mut history);
+        let two = game.board().clone();
+
+        assert_eq!(history.undo().unwrap().board(), &one);
+        assert_eq!(history.undo().unwrap().board(), &start);
+        assert!(history.undo().is_none());
+        assert!(history.can_redo());
+        assert_eq!(history.redo().unwrap().board(), &one);
+        let game = history.redo().unwrap();
+        assert_eq!(game.board(), &two);
+        assert_eq!(game.pieces(), 2);
+        assert!(history.redo().is_none());
+    }
+
+    #[test]
+    fn a_new_placement_forgets_what_was_undone() {
+        let mut game = zen();
+        let mut history = History::new(&game);
+        drop_piece(&mut game, &mut history);
+        drop_piece(&mut game, &mut history);
+        let mut game = history.undo().unwrap();
+        assert!(history.can_redo());
+        game.input(Input::Press(Action::Left));
+        drop_piece(&mut game, &mut history);
+        assert!(!history.can_redo());
+        assert_eq!(history.undo().unwrap().pieces(), 1);
+    }
+
+    #[test]
+    fn only_practice_games_pick_pieces_and_clear_the_board() {
+        let mut game = zen();
+        game.set_next_piece(PieceKind::I);
+        assert_eq!(game.queue().next(), Some(PieceKind::I));
+        game.input(Input::Press(Action::HardDrop));
+        assert_eq!(game.current_piece().kind, PieceKind::I);
+        assert!(!game.board().is_empty());
+        game.clear_board();
+        assert!(game.board().is_empty());
+        assert_eq!(game.current_piece().kind, PieceKind::I);
+
+        let mut marathon = Game::new(&GameConfig { seed: 6, ..GameConfig::default() });
+        let queue: Vec<PieceKind> = marathon.queue().collect();
+        let next = PieceKind::ALL.into_iter().find(|kind| *kind != queue[0]).unwrap();
+        marathon.set_next_piece(next);
+        assert_eq!(marathon.queue().collect::<Vec<_>>(), queue);
+        marathon.input(Input::Press(Action::HardDrop));
+        marathon.clear_board();
+        assert!(!marathon.board().is_empty());
+    }
+}
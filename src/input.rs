@@ -0,0 +1,200 @@
+// Everything a player can do to the current piece.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Left,
+    Right,
+    SoftDrop,
+    HardDrop,
+    RotateClockwise,
+    RotateCounterClockwise,
+    Rotate180,
+    Hold,
+}
+
+impl Action {
+
+    pub const ALL: [Action; 8] = [
+        Action::Left,
+        Action::Right,
+        Action::SoftDrop,
+        Action::HardDrop,
+        Action::RotateClockwise,
+        Action::RotateCounterClockwise,
+        Action::Rotate180,
+        Action::Hold,
+    ];
+
+    // Position in ALL, used to store actions in replays.
+    pub fn index(self) -> usize {
+        Action::ALL.iter().position(|action| *action == self).unwrap()
+    }
+
+    // Actions that keep acting while held, their release matters to the game.
+    pub fn is_held(self) -> bool {
+        matches!(self, Action::Left | Action::Right | Action::SoftDrop)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Input {
+    Press(Action),
+    Release(Action),
+}
+
+// Keys whose held state is tracked by the game itself instead of relying on the
+// key repeat of the terminal, so that DAS and ARR behave the same everywhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeldKey {
+    Left,
+    Right,
+    SoftDrop,
+}
+
+impl HeldKey {
+
+    pub fn from_action(action: Action) -> Option<HeldKey> {
+        match action {
+            Action::Left => Some(HeldKey::Left),
+            Action::Right => Some(HeldKey::Right),
+            Action::SoftDrop => Some(HeldKey::SoftDrop),
+            _ => None,
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            HeldKey::Left => 0,
+            HeldKey::Right => 1,
+            HeldKey::SoftDrop => 2,
+        }
+    }
+
+    fn opposite(self) -> HeldKey {
+        match self {
+            HeldKey::Left => HeldKey::Right,
+            HeldKey::Right => HeldKey::Left,
+            HeldKey::SoftDrop => HeldKey::SoftDrop,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AutoRepeat {
+    // ticks a direction has to be held before it starts repeating
+    das: u32,
+    // ticks between two repeated moves, 0 moves all the way to the wall
+    arr: u32,
+    held: [bool; 3],
+    direction: Option<HeldKey>,
+    charge: u32,
+}
+
+impl AutoRepeat {
+
+    pub fn new(das: u32, arr: u32) -> AutoRepeat {
+        AutoRepeat {
+            das,
+            arr,
+            held: [false; 3],
+            direction: None,
+            charge: 0,
+        }
+    }
+
+    pub fn press(&mut self, key: HeldKey) {
+        self.held[key.index()] = true;
+        if key != HeldKey::SoftDrop {
+            self.direction = Some(key);
+            self.charge = 0;
+        }
+    }
+
+    pub fn release(&mut self, key: HeldKey) {
+        self.held[key.index()] = false;
+    }
+
+    pub fn is_held(&self, key: HeldKey) -> bool {
+        self.held[key.index()]
+    }
+
+    pub fn release_all(&mut self) {
+        self.held = [false; 3];
+        self.direction = None;
+    }
+
+    // Advances by one tick. Returns the direction and number of cells to shift, if any.
+    pub fn tick(&mut self) -> Option<(HeldKey, u32)> {
+        let mut direction = self.direction?;
+        if !self.is_held(direction) {
+            // the opposite direction takes over again if it is still held
+            direction = direction.opposite();
+            if !self.is_held(direction) {
+                self.direction = None;
+                return None;
+            }
+            self.direction = Some(direction);
+            self.charge = 0;
+        }
+
+        self.charge += 1;
+        if self.charge < self.das {
+            return None;
+        }
+        if self.arr == 0 {
+            return Some((direction, u32::MAX));
+        }
+        if (self.charge - self.das).is_multiple_of(self.arr) {
+            return Some((direction, 1));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The shifts of `ticks` ticks, with the tick each happened on.
+    fn run(auto_repeat: &mut AutoRepeat, ticks: u32) -> Vec<(u32, HeldKey, u32)> {
+        (1..=ticks).filter_map(|tick| auto_repeat.tick().map(|(key, cells)| (tick, key, cells))).collect()
+    }
+
+    #[test]
+    fn taps_never_repeat() {
+        let mut auto_repeat = AutoRepeat::new(10, 2);
+        auto_repeat.press(HeldKey::Left);
+        auto_repeat.release(HeldKey::Left);
+        assert_eq!(run(&mut auto_repeat, 60), []);
+    }
+
+    #[test]
+    fn held_keys_repeat_after_das_every_arr() {
+        let mut auto_repeat = AutoRepeat::new(10, 2);
+        auto_repeat.press(HeldKey::Right);
+        let shifts = run(&mut auto_repeat, 15);
+        assert_eq!(shifts, [(10, HeldKey::Right, 1), (12, HeldKey::Right, 1), (14, HeldKey::Right, 1)]);
+        auto_repeat.release(HeldKey::Right);
+        assert_eq!(run(&mut auto_repeat, 15), []);
+
+        let mut instant = AutoRepeat::new(10, 0);
+        instant.press(HeldKey::Left);
+        assert_eq!(run(&mut instant, 10), [(10, HeldKey::Left, u32::MAX)]);
+    }
+
+    #[test]
+    fn the_last_direction_pressed_wins() {
+        let mut auto_repeat = AutoRepeat::new(10, 2);
+        auto_repeat.press(HeldKey::Left);
+        run(&mut auto_repeat, 5);
+        // the opposite direction charges from scratch
+        auto_repeat.press(HeldKey::Right);
+        assert_eq!(run(&mut auto_repeat, 10), [(10, HeldKey::Right, 1)]);
+        // and once it is let go the direction still held takes over, charging again
+        auto_repeat.release(HeldKey::Right);
+        assert_eq!(run(&mut auto_repeat, 10), [(10, HeldKey::Left, 1)]);
+        // a tap of the other direction interrupts a held one the same way
+        auto_repeat.press(HeldKey::Right);
+        auto_repeat.release(HeldKey::Right);
+        assert_eq!(run(&mut auto_repeat, 10), [(10, HeldKey::Left, 1)]);
+    }
+}
@@ -0,0 +1,155 @@
+use std::time::{Duration, Instant};
+
+use tetris::input::{Action, Input};
+
+// Without release events a key only counts as held once the terminal repeats it, and as
+// released once the repeats stop. Until the first repeat, which comes after the initial delay
+// of the OS key repeat, another press of the key is taken as that repeat.
+const FIRST_REPEAT_TIMEOUT: Duration = Duration::from_millis(600);
+const RELEASE_TIMEOUT: Duration = Duration::from_millis(100);
+
+// Turns terminal key events into game inputs. Terminals without the kitty keyboard
+// protocol never report releases, for those the releases of held keys are guessed.
+#[derive(Debug)]
+pub struct KeyTracker {
+    release_events: bool,
+    // keys pressed, when they were last seen and whether the terminal repeats them yet
+    held: Vec<(Action, Instant, bool)>,
+}
+
+impl KeyTracker {
+
+    pub fn new(release_events: bool) -> KeyTracker {
+        KeyTracker {
+            release_events,
+            held: vec![],
+        }
+    }
+
+    // The inputs of a key press, none if it is only the terminal repeating a held key.
+    // Without release events a press is released right away, a tap must not charge DAS,
+    // and only pressed again as held once the terminal repeats it.
+    pub fn press(&mut self, action: Action, now: Instant) -> Vec<Input> {
+        if !action.is_held() {
+            return vec![Input::Press(action)];
+        }
+        if let Some((_, seen, repeating)) = self.held.iter_mut().find(|(held, ..)| *held == action) {
+            *seen = now;
+            if self.release_events || *repeating {
+                return vec![];
+            }
+            *repeating = true;
+            return vec![Input::Press(action)];
+        }
+        self.held.push((action, now, false));
+        if self.release_events {
+            return vec![Input::Press(action)];
+        }
+        vec![Input::Press(action), Input::Release(action)]
+    }
+
+    pub fn release(&mut self, action: Action) -> Option<Input> {
+        let index = self.held.iter().position(|(held, ..)| *held == action)?;
+        self.held.remove(index);
+        Some(Input::Release(action))
+    }
+
+    // Releases held keys the terminal stopped repeating.
+    pub fn expire(&mut self, now: Instant) -> Vec<Input> {
+        if self.release_events {
+            return vec![];
+        }
+        let mut released = vec![];
+        self.held.retain(|(action, seen, repeating)| {
+            let timeout = if *repeating { RELEASE_TIMEOUT } else { FIRST_REPEAT_TIMEOUT };
+            let held = now.duration_since(*seen) <= timeout;
+            // keys that were never repeated are released already
+            if !held && *repeating {
+                released.push(Input::Release(*action));
+            }
+            held
+        });
+        released
+    }
+
+    pub fn clear(&mut self) {
+        self.held.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tetris::config::GameConfig;
+    use tetris::game::Game;
+
+    const REPEAT_DELAY: Duration = Duration::from_millis(500);
+    const REPEAT_INTERVAL: Duration = Duration::from_millis(33);
+
+    #[test]
+    fn taps_are_released_right_away() {
+        let start = Instant::now();
+        let mut keys = KeyTracker::new(false);
+        assert_eq!(keys.press(Action::Left, start), [Input::Press(Action::Left), Input::Release(Action::Left)]);
+        assert_eq!(keys.expire(start + Duration::from_millis(300)), []);
+        assert_eq!(keys.expire(start + Duration::from_secs(1)), []);
+        // a tap well after the last one is a tap again
+        let later = start + Duration::from_secs(2);
+        assert_eq!(keys.press(Action::Left, later), [Input::Press(Action::Left), Input::Release(Action::Left)]);
+        assert_eq!(keys.press(Action::HardDrop, later), [Input::Press(Action::HardDrop)]);
+    }
+
+    #[test]
+    fn keys_are_held_from_the_first_repeat_until_the_repeats_stop() {
+        let start = Instant::now();
+        let mut keys = KeyTracker::new(false);
+        keys.press(Action::SoftDrop, start);
+        let mut now = start + REPEAT_DELAY;
+        assert_eq!(keys.press(Action::SoftDrop, now), [Input::Press(Action::SoftDrop)]);
+        for _ in 0..10 {
+            now += REPEAT_INTERVAL;
+            assert_eq!(keys.press(Action::SoftDrop, now), []);
+            assert_eq!(keys.expire(now), []);
+        }
+        assert_eq!(keys.expire(now + REPEAT_INTERVAL), []);
+        assert_eq!(keys.expire(now + RELEASE_TIMEOUT * 2), [Input::Release(Action::SoftDrop)]);
+    }
+
+    #[test]
+    fn tapping_the_other_direction_keeps_the_held_one() {
+        let start = Instant::now();
+        let mut keys = KeyTracker::new(false);
+        keys.press(Action::Left, start);
+        let now = start + REPEAT_DELAY;
+        keys.press(Action::Left, now);
+        assert_eq!(keys.press(Action::Right, now), [Input::Press(Action::Right), Input::Release(Action::Right)]);
+        assert_eq!(keys.press(Action::Left, now + REPEAT_INTERVAL), []);
+        assert_eq!(keys.expire(now + REPEAT_INTERVAL * 2), []);
+    }
+
+    #[test]
+    fn release_events_are_passed_on() {
+        let start = Instant::now();
+        let mut keys = KeyTracker::new(true);
+        assert_eq!(keys.press(Action::Right, start), [Input::Press(Action::Right)]);
+        assert_eq!(keys.press(Action::Right, start + REPEAT_DELAY), []);
+        assert_eq!(keys.expire(start + Duration::from_secs(5)), []);
+        assert_eq!(keys.release(Action::Right), Some(Input::Release(Action::Right)));
+        assert_eq!(keys.release(Action::Right), None);
+    }
+
+    #[test]
+    fn a_tap_moves_the_piece_one_cell() {
+        let mut game = Game::new(&GameConfig::default());
+        let x = game.current_piece().x;
+        let mut keys = KeyTracker::new(false);
+        for input in keys.press(Action::Left, Instant::now()) {
+            game.input(input);
+        }
+        // as long as a key repeat takes to start
+        for _ in 0..36 {
+            game.tick();
+        }
+        assert_eq!(game.current_piece().x, x - 1);
+    }
+}
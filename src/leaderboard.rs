@@ -0,0 +1,355 @@
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tetris::encoding::{read_str, read_u32, read_u64, write_str, write_u32, write_u64};
+use tetris::game::Game;
+use tetris::mode::GameMode;
+
+use crate::read_write::*;
+
+// entries kept per mode
+pub const MAX_ENTRIES: usize = 10;
+pub const MAX_NAME_LEN: usize = 16;
+
+// A finished run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub score: u64,
+    pub lines: u32,
+    pub level: u32,
+    pub duration_ms: u64,
+    // seconds since the unix epoch
+    pub date: u64,
+    pub seed: u64,
+    pub pieces: u32,
+}
+
+impl Entry {
+
+    pub fn from_game(game: &Game) -> Entry {
+        Entry {
+            name: String::new(),
+            score: game.score(),
+            lines: game.lines(),
+            level: game.level(),
+            duration_ms: game.time_ms(),
+            date: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs()),
+            seed: game.seed(),
+            pieces: game.pieces(),
+        }
+    }
+
+    // pieces per second
+    pub fn pps(&self) -> f64 {
+        if self.duration_ms == 0 {
+            return 0.0;
+        }
+        self.pieces as f64 * 1000.0 / self.duration_ms as f64
+    }
+
+    fn write(&self, writer: &mut impl io::Write) -> io::Result<()> {
+        write_str(writer, &self.name)?;
+        write_u64(writer, self.score)?;
+        write_u32(writer, self.lines)?;
+        write_u32(writer, self.level)?;
+        write_u64(writer, self.duration_ms)?;
+        write_u64(writer, self.date)?;
+        write_u64(writer, self.seed)?;
+        write_u32(writer, self.pieces)
+    }
+
+    fn read(reader: &mut impl io::Read) -> io::Result<Entry> {
+        Ok(Entry {
+            name: read_str(reader)?,
+            score: read_u64(reader)?,
+            lines: read_u32(reader)?,
+            level: read_u32(reader)?,
+            duration_ms: read_u64(reader)?,
+            date: read_u64(reader)?,
+            seed: read_u64(reader)?,
+            pieces: read_u32(reader)?,
+        })
+    }
+}
+
+// Whether `entry` ranks above `other` in `mode`, on a tie the older run stays ahead.
+fn ranks_above(mode: GameMode, entry: &Entry, other: &Entry) -> bool {
+    if mode.ranks_by_time() {
+        entry.duration_ms < other.duration_ms
+    }
+    else {
+        entry.score > other.score
+    }
+}
+
+// The best runs of every mode, best first.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Leaderboard {
+    boards: Vec<(GameMode, Vec<Entry>)>,
+    // offered again in the name prompt
+    pub last_name: String,
+}
+
+impl Leaderboard {
+
+    // A missing file is an empty leaderboard, a damaged one is moved aside and reset.
+    pub fn load(path: &Path) -> io::Result<Leaderboard> {
+        let Some(payload) = load_data(path)? else {
+            return Ok(Leaderboard::default());
+        };
+        match Leaderboard::decode(&payload) {
+            Ok(leaderboard) => Ok(leaderboard),
+            Err(err) if err.kind() == io::ErrorKind::InvalidData || err.kind() == io::ErrorKind::UnexpectedEof => {
+                backup(path)?;
+                Ok(Leaderboard::default())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        save_data(path, &self.encode())
+    }
+
+    pub fn entries(&self, mode: GameMode) -> &[Entry] {
+        self.boards.iter()
+            .find(|(board_mode, _)| *board_mode == mode)
+            .map_or(&[], |(_, entries)| entries.as_slice())
+    }
+
+    // The place `entry` would take, None if it doesn't make the top MAX_ENTRIES.
+    pub fn rank(&self, mode: GameMode, entry: &Entry) -> Option<usize> {
+        let rank = self.entries(mode).iter()
+            .position(|other| ranks_above(mode, entry, other))
+            .unwrap_or(self.entries(mode).len());
+        (rank < MAX_ENTRIES).then_some(rank)
+    }
+
+    pub fn insert(&mut self, mode: GameMode, entry: Entry) -> Option<usize> {
+        let rank = self.rank(mode, &entry)?;
+        let index = match self.boards.iter().position(|(board_mode, _)| *board_mode == mode) {
+            Some(index) => index,
+            None => {
+                self.boards.push((mode, vec![]));
+                self.boards.len() - 1
+            }
+        };
+        let entries = &mut self.boards[index].1;
+        entries.insert(rank, entry);
+        entries.truncate(MAX_ENTRIES);
+        Some(rank)
+    }
+
+    // The last name, then per mode its name and entries.
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        // writing to a Vec can't fail
+        let _ = self.write_to(&mut bytes);
+        bytes
+    }
+
+    fn write_to(&self, bytes: &mut Vec<u8>) -> io::Result<()> {
+        write_str(bytes, &self.last_name)?;
+        write_u32(bytes, self.boards.len() as u32)?;
+        for (mode, entries) in &self.boards {
+            write_str(bytes, mode.name())?;
+            write_u32(bytes, entries.len() as u32)?;
+            for entry in entries {
+                entry.write(bytes)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn decode(mut payload: &[u8]) -> io::Result<Leaderboard> {
+        let reader = &mut payload;
+        let mut leaderboard = Leaderboard {
+            last_name: read_str(reader)?,
+            ..Leaderboard::default()
+        };
+        for _ in 0..read_u32(reader)? {
+            let mode = read_str(reader)?;
+            let mut entries = vec![];
+            for _ in 0..read_u32(reader)? {
+                entries.push(Entry::read(reader)?);
+            }
+            // boards of modes this version doesn't know are dropped
+            if let Ok(mode) = mode.parse() {
+                entries.truncate(MAX_ENTRIES);
+                leaderboard.boards.push((mode, entries));
+            }
+        }
+        Ok(leaderboard)
+    }
+}
+
+// The best run of every mode on this machine, whether or not it was entered in the
+// leaderboard. Kept in its own file so that it survives a reset of the leaderboard.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PersonalBests {
+    bests: Vec<(GameMode, Entry)>,
+}
+
+impl PersonalBests {
+
+    pub fn load(path: &Path) -> io::Result<PersonalBests> {
+        let Some(payload) = load_data(path)? else {
+            return Ok(PersonalBests::default());
+        };
+        match PersonalBests::decode(&payload) {
+            Ok(bests) => Ok(bests),
+            Err(err) if err.kind() == io::ErrorKind::InvalidData || err.kind() == io::ErrorKind::UnexpectedEof => {
+                backup(path)?;
+                Ok(PersonalBests::default())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut bytes = vec![];
+        write_u32(&mut bytes, self.bests.len() as u32)?;
+        for (mode, entry) in &self.bests {
+            write_str(&mut bytes, mode.name())?;
+            entry.write(&mut bytes)?;
+        }
+        save_data(path, &bytes)
+    }
+
+    fn decode(mut payload: &[u8]) -> io::Result<PersonalBests> {
+        let reader = &mut payload;
+        let mut bests = PersonalBests::default();
+        for _ in 0..read_u32(reader)? {
+            let mode = read_str(reader)?;
+            let entry = Entry::read(reader)?;
+            if let Ok(mode) = mode.parse() {
+                bests.bests.push((mode, entry));
+            }
+        }
+        Ok(bests)
+    }
+
+    pub fn get(&self, mode: GameMode) -> Option<&Entry> {
+        self.bests.iter().find(|(best_mode, _)| *best_mode == mode).map(|(_, entry)| entry)
+    }
+
+    // Keeps `entry` if it beats the best of its mode, returns true if it did.
+    pub fn update(&mut self, mode: GameMode, entry: &Entry) -> bool {
+        match self.bests.iter_mut().find(|(best_mode, _)| *best_mode == mode) {
+            Some((_, best)) if ranks_above(mode, entry, best) => *best = entry.clone(),
+            Some(_) => return false,
+            None => self.bests.push((mode, entry.clone())),
+        }
+        true
+    }
+}
+
+// YYYY-MM-DD in UTC.
+pub fn format_date(date: u64) -> String {
+    // days to civil date, from Howard Hinnant's date algorithms
+    let days = (date / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+// Minutes, seconds and milliseconds.
+pub fn format_duration(ms: u64) -> String {
+    format!("{}:{:02}.{:03}", ms / 60000, ms / 1000 % 60, ms % 1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    fn entry(name: &str, score: u64, duration_ms: u64) -> Entry {
+        Entry {
+            name: name.to_string(),
+            score,
+            lines: 40,
+            level: 5,
+            duration_ms,
+            date: 1_700_000_000,
+            seed: 9,
+            pieces: 100,
+        }
+    }
+
+    #[test]
+    fn leaderboards_decode_to_what_was_encoded() {
+        let mut leaderboard = Leaderboard { last_name: "ännie".to_string(), ..Leaderboard::default() };
+        leaderboard.insert(GameMode::Marathon, entry("a", 500, 60_000));
+        leaderboard.insert(GameMode::Marathon, entry("b", 900, 60_000));
+        leaderboard.insert(GameMode::Sprint, entry("c", 0, 50_000));
+        let decoded = Leaderboard::decode(&leaderboard.encode()).unwrap();
+        assert_eq!(decoded, leaderboard);
+
+        // names are cut off at a whole character within 255 bytes
+        leaderboard.insert(GameMode::Marathon, entry(&"ä".repeat(200), 1000, 60_000));
+        let decoded = Leaderboard::decode(&leaderboard.encode()).unwrap();
+        assert_eq!(decoded.entries(GameMode::Marathon)[0].name, "ä".repeat(127));
+
+        let error = Leaderboard::decode(&leaderboard.encode()[..20]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn only_the_best_entries_are_kept() {
+        let mut leaderboard = Leaderboard::default();
+        for score in 0..MAX_ENTRIES as u64 + 5 {
+            leaderboard.insert(GameMode::Marathon, entry("", score * 100, 0));
+        }
+        let scores: Vec<u64> = leaderboard.entries(GameMode::Marathon).iter().map(|entry| entry.score).collect();
+        let expected: Vec<u64> = (5..MAX_ENTRIES as u64 + 5).rev().map(|score| score * 100).collect();
+        assert_eq!(scores, expected);
+        assert_eq!(leaderboard.rank(GameMode::Marathon, &entry("", 450, 0)), None);
+        assert_eq!(leaderboard.insert(GameMode::Marathon, entry("", 450, 0)), None);
+        assert_eq!(leaderboard.insert(GameMode::Marathon, entry("", 1050, 0)), Some(4));
+        assert_eq!(leaderboard.entries(GameMode::Marathon).len(), MAX_ENTRIES);
+        assert!(leaderboard.entries(GameMode::Sprint).is_empty());
+    }
+
+    #[test]
+    fn ties_keep_the_older_entry_ahead() {
+        let mut leaderboard = Leaderboard::default();
+        leaderboard.insert(GameMode::Marathon, entry("first", 100, 0));
+        assert_eq!(leaderboard.insert(GameMode::Marathon, entry("second", 100, 0)), Some(1));
+        // sprints rank by time, the faster run first
+        leaderboard.insert(GameMode::Sprint, entry("slow", 900, 70_000));
+        assert_eq!(leaderboard.insert(GameMode::Sprint, entry("fast", 100, 50_000)), Some(0));
+        assert_eq!(leaderboard.insert(GameMode::Sprint, entry("tie", 100, 50_000)), Some(1));
+        let names: Vec<&str> = leaderboard.entries(GameMode::Sprint).iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, ["fast", "tie", "slow"]);
+    }
+
+    #[test]
+    fn damaged_leaderboards_are_backed_up_and_reset() {
+        let dir = env::temp_dir().join(format!("tetris-{}-leaderboard", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("leaderboard.bin");
+        assert_eq!(Leaderboard::load(&path).unwrap(), Leaderboard::default());
+
+        let mut leaderboard = Leaderboard::default();
+        leaderboard.insert(GameMode::Ultra, entry("u", 1234, 120_000));
+        leaderboard.save(&path).unwrap();
+        assert_eq!(Leaderboard::load(&path).unwrap(), leaderboard);
+
+        // a valid save file whose payload isn't a leaderboard
+        save_data(&path, &[1, 2, 3]).unwrap();
+        assert_eq!(Leaderboard::load(&path).unwrap(), Leaderboard::default());
+        assert!(!path.exists());
+        assert!(dir.join("leaderboard.bin.bak").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
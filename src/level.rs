@@ -0,0 +1,53 @@
+// Gravity is measured in 1/65536 cells per tick, so that sub-cell speeds add up exactly.
+pub const GRAVITY_UNIT: u32 = 65536;
+// 20G: the piece falls through the whole visible field within one tick
+pub const MAX_GRAVITY: u32 = 20 * GRAVITY_UNIT;
+pub const MAX_LEVEL: u32 = 20;
+
+// Guideline curve, (0.8 - (level - 1) * 0.007)^(level - 1) seconds per row at 60 ticks per second.
+const GRAVITY_TABLE: [u32; 19] = [
+    1092, 1377, 1768, 2311, 3075, 4169, 5759, 8107, 11634, 17026,
+    25416, 38709, 60169, 95483, 154742, 256187, 433425, 749597, 1310720,
+];
+
+pub fn gravity(level: u32) -> u32 {
+    let index = level.clamp(1, MAX_LEVEL) as usize - 1;
+    GRAVITY_TABLE.get(index).copied().unwrap_or(MAX_GRAVITY)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Level {
+    start: u32,
+    lines: u32,
+    lines_per_level: u32,
+}
+
+impl Level {
+
+    pub fn new(start: u32, lines_per_level: u32) -> Level {
+        Level {
+            start: start.clamp(1, MAX_LEVEL),
+            lines: 0,
+            lines_per_level: lines_per_level.max(1),
+        }
+    }
+
+    pub fn level(&self) -> u32 {
+        self.start + self.lines / self.lines_per_level
+    }
+
+    pub fn lines(&self) -> u32 {
+        self.lines
+    }
+
+    pub fn gravity(&self) -> u32 {
+        gravity(self.level())
+    }
+
+    // Counts cleared lines and returns true if that reached a new level.
+    pub fn add_lines(&mut self, lines: u32) -> bool {
+        let level = self.level();
+        self.lines += lines;
+        self.level() > level
+    }
+}
@@ -0,0 +1,16 @@
+pub mod board;
+pub mod bot;
+pub mod config;
+pub mod encoding;
+pub mod game;
+pub mod garbage;
+pub mod history;
+pub mod input;
+pub mod level;
+pub mod mode;
+pub mod piece;
+pub mod protocol;
+pub mod randomizer;
+pub mod replay;
+pub mod scoring;
+pub mod srs;
@@ -1,36 +1,60 @@
 use app::App;
-use color_eyre::Result;
-
-use std::fs::File;
-use std::path::Path;
+use color_eyre::{eyre::WrapErr, Result};
 
 use read_write::*;
+use settings::Settings;
 
 pub mod errors;
 pub mod tui;
 pub mod app;
+pub mod keys;
+pub mod leaderboard;
+pub mod net;
 pub mod read_write;
+pub mod settings;
+pub mod view;
 
 fn main() -> Result<()> {
+    let settings = Settings::from_args(std::env::args().skip(1))?;
     errors::install_hooks()?;
-    let mut terminal = tui::init()?;
 
-    let path = Path::new("Highscore.bin");
-    let number: u64;
-    if !path.exists() {
-        File::create(path)?;
-        number = 0;
+    // the other player is found before the terminal is taken over, so that Ctrl-C still works
+    let online = if let Some(port) = settings.host {
+        let config = settings.game_config();
+        println!("Waiting for another player on port {port}...");
+        let (connection, spectators) = net::host(port, &config, settings.spectator_delay)
+            .wrap_err("hosting the game failed")?;
+        Some((connection, Some(spectators), config))
     }
-    else {
-        number = read(&path)?;
+    else if let Some(address) = &settings.connect {
+        println!("Connecting to {address}...");
+        let (connection, config) = net::connect(address)
+            .wrap_err_with(|| format!("connecting to {address} failed"))?;
+        Some((connection, None, config))
+    }
+    else if let Some(address) = &settings.spectate {
+        println!("Waiting for a game at {address}...");
+        let (connection, config) = net::spectate(address)
+            .wrap_err_with(|| format!("watching {address} failed"))?;
+        Some((connection, None, config))
     }
+    else {
+        None
+    };
+    let mut terminal = tui::init()?;
 
-    let mut app = App::new()?;
+    let path = highscore_path()?;
+    let number = read(&path)?;
+
+    let mut app = App::new(&settings)?;
     app.highscore = number;
+    if let Some((connection, spectators, config)) = online {
+        app.go_online(connection, spectators, config);
+    }
     app.run(&mut terminal)?;
     tui::restore()?;
     
-    save(path, app.highscore)?;
+    save(&path, app.highscore)?;
     Ok(())
 }
 
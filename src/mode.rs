@@ -0,0 +1,103 @@
+use std::str::FromStr;
+
+// The rule sets a game can be played with, each has its own leaderboard.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameMode {
+    // endless, the speed goes up with the level until the player tops out
+    #[default]
+    Marathon,
+    // clear SPRINT_LINES lines as fast as possible
+    Sprint,
+    // score as much as possible before the time limit of the config runs out
+    Ultra,
+    // dig through rows of garbage as fast as possible
+    Dig,
+    // practice without gravity or game over, placements can be undone
+    Zen,
+    // two players on the same pieces, cleared lines send garbage to the opponent
+    Versus,
+}
+
+pub const SPRINT_LINES: u32 = 40;
+
+impl GameMode {
+
+    pub const ALL: [GameMode; 6] = [
+        GameMode::Marathon, GameMode::Sprint, GameMode::Ultra, GameMode::Dig, GameMode::Zen, GameMode::Versus,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Marathon => "marathon",
+            GameMode::Sprint => "sprint",
+            GameMode::Ultra => "ultra",
+            GameMode::Dig => "dig",
+            GameMode::Zen => "zen",
+            GameMode::Versus => "versus",
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            GameMode::Marathon => "Marathon",
+            GameMode::Sprint => "Sprint",
+            GameMode::Ultra => "Ultra",
+            GameMode::Dig => "Dig",
+            GameMode::Zen => "Zen",
+            GameMode::Versus => "Versus",
+        }
+    }
+
+    // Lines that finish the game once cleared.
+    pub fn line_goal(self) -> Option<u32> {
+        match self {
+            GameMode::Sprint => Some(SPRINT_LINES),
+            GameMode::Marathon | GameMode::Ultra | GameMode::Dig | GameMode::Zen | GameMode::Versus => None,
+        }
+    }
+
+    // The game ends after `time_limit_ms` of the config.
+    pub fn has_time_limit(self) -> bool {
+        self == GameMode::Ultra
+    }
+
+    // Runs are ranked by how fast they reached the goal instead of by score.
+    pub fn ranks_by_time(self) -> bool {
+        matches!(self, GameMode::Sprint | GameMode::Dig)
+    }
+
+    // Pieces only move on input and lock on a hard drop, topping out clears the board
+    // instead of ending the game, and the next piece can be picked by hand.
+    pub fn is_practice(self) -> bool {
+        self == GameMode::Zen
+    }
+
+    // Cleared lines attack an opponent and received garbage rises from below,
+    // the last player standing wins.
+    pub fn is_versus(self) -> bool {
+        self == GameMode::Versus
+    }
+
+    // Runs of the mode go into the leaderboard.
+    pub fn is_ranked(self) -> bool {
+        !self.is_practice() && !self.is_versus()
+    }
+
+    // The board starts with `garbage_rows` of the config, the game ends once they are cleared.
+    pub fn starts_with_garbage(self) -> bool {
+        self == GameMode::Dig
+    }
+}
+
+impl FromStr for GameMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        GameMode::ALL.into_iter()
+            .find(|mode| mode.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<&str> = GameMode::ALL.iter().map(|mode| mode.name()).collect();
+                format!("unknown mode {s:?}, expected one of {}", names.join(", "))
+            })
+    }
+}
@@ -0,0 +1,430 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+use tetris::config::GameConfig;
+use tetris::encoding::invalid;
+use tetris::protocol::{Message, VERSION};
+
+// a connection that stays silent for longer counts as lost
+const TIMEOUT: Duration = Duration::from_secs(10);
+// a ping goes out if nothing else was sent for this long
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+// how long closing waits for the last messages to be written
+const CLOSE_TIMEOUT: Duration = Duration::from_millis(500);
+
+// The TCP connection to the other player or a spectator, see tetris::protocol for what goes
+// over it. Once the game runs nothing blocks: messages are queued by `send`, written by `flush`
+// and picked up by `receive`, all three are called once per frame.
+#[derive(Debug)]
+pub struct Connection {
+    stream: TcpStream,
+    host: bool,
+    // one end of the connection only watches
+    spectator: bool,
+    // bytes received but not decoded yet, and bytes not written yet
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+    last_received: Instant,
+    last_sent: Instant,
+    closed: bool,
+}
+
+// Waits for another player to connect to `port` and starts the first game with `config`.
+// Spectators that show up in the meantime are kept for when the game starts.
+pub fn host(port: u16, config: &GameConfig, spectator_delay: Duration) -> io::Result<(Connection, Spectators)> {
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    let mut spectators = Spectators::new(listener.try_clone()?, spectator_delay);
+    let mut connection = loop {
+        let (stream, _) = listener.accept()?;
+        // someone that doesn't speak the protocol isn't worth giving up on the game for
+        match Connection::open(stream, true, false) {
+            Ok(connection) if connection.is_spectator() => spectators.add(connection),
+            Ok(connection) => break connection,
+            Err(_) => {}
+        }
+    };
+    listener.set_nonblocking(true)?;
+    // spectators are silent while they wait for the first game
+    for spectator in &mut spectators.spectators {
+        spectator.connection.last_received = Instant::now();
+    }
+    connection.send(&Message::Start(config.clone()));
+    Ok((connection, spectators))
+}
+
+// Connects to a host, returns the connection and the config of the first game.
+pub fn connect(address: &str) -> io::Result<(Connection, GameConfig)> {
+    join(address, false)
+}
+
+// Connects to a host to watch its games, returns the connection and the config of the current game.
+pub fn spectate(address: &str) -> io::Result<(Connection, GameConfig)> {
+    join(address, true)
+}
+
+fn join(address: &str, spectator: bool) -> io::Result<(Connection, GameConfig)> {
+    let stream = TcpStream::connect(address)?;
+    let mut connection = Connection::open(stream, false, spectator)?;
+    // a spectator may have to wait for the host to find a player
+    if spectator {
+        connection.stream.set_read_timeout(None)?;
+    }
+    loop {
+        match connection.read_blocking()? {
+            Message::Start(config) => {
+                connection.stream.set_nonblocking(true)?;
+                return Ok((connection, config));
+            }
+            // the host keeps spectators alive until the game catches up with the delay
+            Message::Ping => {}
+            Message::Bye => return Err(invalid("the host already has another player")),
+            message => return Err(invalid(format!("expected the host to start a game, got {message:?}"))),
+        }
+    }
+}
+
+impl Connection {
+
+    // Exchanges the protocol versions and whether the guest plays or watches. The stream
+    // is non-blocking afterwards, except for a guest that still has to wait for the first game.
+    fn open(stream: TcpStream, host: bool, spectator: bool) -> io::Result<Connection> {
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        let mut connection = Connection::new(stream, host, spectator);
+        connection.stream.write_all(&Message::Hello { version: VERSION, spectator }.encode())?;
+        match connection.read_blocking()? {
+            Message::Hello { version, spectator } if version == VERSION => {
+                connection.spectator |= spectator;
+            }
+            Message::Hello { version, .. } => {
+                return Err(invalid(format!("the other player speaks protocol version {version}, this is {VERSION}")));
+            }
+            message => return Err(invalid(format!("expected a hello, got {message:?}"))),
+        }
+        if host {
+            connection.stream.set_nonblocking(true)?;
+        }
+        Ok(connection)
+    }
+
+    // Takes in a connection to a game that is already running. Nothing blocks, the Hello
+    // of the host is queued and that of the guest has to be picked up with `receive`.
+    fn accept(stream: TcpStream) -> io::Result<Connection> {
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+        let mut connection = Connection::new(stream, true, false);
+        connection.send(&Message::Hello { version: VERSION, spectator: false });
+        Ok(connection)
+    }
+
+    fn new(stream: TcpStream, host: bool, spectator: bool) -> Connection {
+        Connection {
+            stream,
+            host,
+            spectator,
+            incoming: vec![],
+            outgoing: vec![],
+            last_received: Instant::now(),
+            last_sent: Instant::now(),
+            closed: false,
+        }
+    }
+
+    fn read_blocking(&mut self) -> io::Result<Message> {
+        let mut buffer = [0u8; 256];
+        loop {
+            if let Some((message, len)) = Message::decode(&self.incoming)? {
+                self.incoming.drain(..len);
+                return Ok(message);
+            }
+            let len = self.stream.read(&mut buffer)?;
+            if len == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the other player hung up"));
+            }
+            self.incoming.extend_from_slice(&buffer[..len]);
+        }
+    }
+
+    // The host picks the rules and starts every game.
+    pub fn is_host(&self) -> bool {
+        self.host
+    }
+
+    pub fn is_spectator(&self) -> bool {
+        self.spectator
+    }
+
+    // The other player quit, hung up, went silent for too long or sent garbled data.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    pub fn send(&mut self, message: &Message) {
+        if !self.closed {
+            self.outgoing.extend_from_slice(&message.encode());
+        }
+    }
+
+    // Writes as much of the queued messages as the socket takes right now.
+    pub fn flush(&mut self) {
+        if self.outgoing.is_empty() && self.last_sent.elapsed() >= PING_INTERVAL {
+            self.send(&Message::Ping);
+        }
+        while !self.closed && !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => self.closed = true,
+                Ok(len) => {
+                    self.outgoing.drain(..len);
+                    self.last_sent = Instant::now();
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => self.closed = true,
+            }
+        }
+    }
+
+    // The messages that arrived since the last call, in order.
+    pub fn receive(&mut self) -> Vec<Message> {
+        let mut buffer = [0u8; 4096];
+        while !self.closed {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => self.closed = true,
+                Ok(len) => {
+                    self.incoming.extend_from_slice(&buffer[..len]);
+                    self.last_received = Instant::now();
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => self.closed = true,
+            }
+        }
+        if self.last_received.elapsed() > TIMEOUT {
+            self.closed = true;
+        }
+
+        let mut messages = vec![];
+        let mut start = 0;
+        loop {
+            match Message::decode(&self.incoming[start..]) {
+                Ok(Some((message, len))) => {
+                    start += len;
+                    if message == Message::Bye {
+                        self.closed = true;
+                    }
+                    messages.push(message);
+                }
+                Ok(None) => break,
+                Err(_) => {
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+        self.incoming.drain(..start);
+        messages
+    }
+
+    // Says bye and waits a moment until everything queued is written.
+    pub fn close(&mut self) {
+        self.send(&Message::Bye);
+        // a peer that stopped reading mustn't keep this side from quitting
+        if self.stream.set_write_timeout(Some(CLOSE_TIMEOUT)).is_ok() && self.stream.set_nonblocking(false).is_ok() {
+            self.flush();
+        }
+        self.closed = true;
+    }
+}
+
+// A spectator and the index in the log of the next message it gets.
+#[derive(Debug)]
+struct Spectator {
+    connection: Connection,
+    next: usize,
+}
+
+// The spectators of a hosted game. The host logs the messages of both players, spectators get
+// the log of the current game when they join and then every message once `delay` is over.
+#[derive(Debug)]
+pub struct Spectators {
+    listener: TcpListener,
+    // connections that haven't said hello yet
+    pending: Vec<Connection>,
+    spectators: Vec<Spectator>,
+    // what spectators may still need, from the Start of the current game or the oldest
+    // message a spectator hasn't got yet, whichever comes first
+    log: VecDeque<(Instant, Message)>,
+    // index in the log of the Start of the current game
+    game_start: usize,
+    delay: Duration,
+}
+
+impl Spectators {
+
+    fn new(listener: TcpListener, delay: Duration) -> Spectators {
+        Spectators {
+            listener,
+            pending: vec![],
+            spectators: vec![],
+            log: VecDeque::new(),
+            game_start: 0,
+            delay,
+        }
+    }
+
+    fn add(&mut self, connection: Connection) {
+        self.spectators.push(Spectator { connection, next: self.game_start });
+    }
+
+    pub fn len(&self) -> usize {
+        self.spectators.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spectators.is_empty()
+    }
+
+    // Logs the Start of a game.
+    pub fn start(&mut self, config: &GameConfig) {
+        self.game_start = self.log.len();
+        self.log.push_back((Instant::now(), Message::Start(config.clone())));
+    }
+
+    // Logs a message of one of the players, 0 is the host.
+    pub fn relay(&mut self, player: u8, message: &Message) {
+        if message.is_game() {
+            self.log.push_back((Instant::now(), Message::Relay { player, message: Box::new(message.clone()) }));
+        }
+    }
+
+    // Takes in new spectators and sends everything that is due.
+    pub fn update(&mut self) {
+        while let Ok((stream, _)) = self.listener.accept() {
+            if let Ok(connection) = Connection::accept(stream) {
+                self.pending.push(connection);
+            }
+        }
+        // the game must not wait for someone to say hello, the handshake takes as many frames as it needs
+        for mut connection in std::mem::take(&mut self.pending) {
+            match connection.receive().first() {
+                Some(Message::Hello { version: VERSION, spectator: true }) => {
+                    connection.spectator = true;
+                    self.add(connection);
+                }
+                // the game already has its players, or the other side doesn't speak the protocol
+                Some(_) => {
+                    connection.send(&Message::Bye);
+                    connection.flush();
+                }
+                None if connection.is_closed() => {}
+                None => {
+                    connection.flush();
+                    self.pending.push(connection);
+                }
+            }
+        }
+        let now = Instant::now();
+        for spectator in &mut self.spectators {
+            // nothing but pings and byes come from spectators
+            spectator.connection.receive();
+            while let Some((time, message)) = self.log.get(spectator.next) {
+                if now.duration_since(*time) < self.delay {
+                    break;
+                }
+                spectator.connection.send(message);
+                spectator.next += 1;
+            }
+            spectator.connection.flush();
+        }
+        self.spectators.retain(|spectator| !spectator.connection.is_closed());
+
+        let needed = self.spectators.iter().map(|spectator| spectator.next).fold(self.game_start, usize::min);
+        self.log.drain(..needed);
+        self.game_start -= needed;
+        for spectator in &mut self.spectators {
+            spectator.next -= needed;
+        }
+    }
+
+    pub fn close(&mut self) {
+        for spectator in &mut self.spectators {
+            spectator.connection.close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use tetris::input::{Action, Input};
+    use tetris::replay::TimedInput;
+
+    // Receives until a message arrives, gives up after a few seconds.
+    fn receive_some(connection: &mut Connection) -> Vec<Message> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            let messages: Vec<Message> = connection.receive().into_iter().filter(|message| *message != Message::Ping).collect();
+            if !messages.is_empty() {
+                return messages;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        panic!("nothing arrived in time");
+    }
+
+    #[test]
+    fn players_and_spectators_meet_over_loopback() {
+        let port = TcpListener::bind(("127.0.0.1", 0)).unwrap().local_addr().unwrap().port();
+        let config = GameConfig { seed: 11, ..GameConfig::default() };
+        let host_config = config.clone();
+        let host = thread::spawn(move || {
+            let (mut connection, mut spectators) = host(port, &host_config, Duration::ZERO).unwrap();
+            spectators.start(&host_config);
+            // the Start has to go out before the guest can return from connecting
+            connection.flush();
+            (connection, spectators)
+        });
+        let address = format!("127.0.0.1:{port}");
+        let (mut guest, guest_config) = loop {
+            match connect(&address) {
+                Ok(joined) => break joined,
+                // the host may not be listening yet
+                Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => thread::sleep(Duration::from_millis(10)),
+                Err(err) => panic!("{err}"),
+            }
+        };
+        let (mut connection, mut spectators) = host.join().unwrap();
+        assert!(connection.is_host() && !guest.is_host());
+        assert_eq!(guest_config, config);
+
+        let input = Message::Input(TimedInput { tick: 3, input: Input::Press(Action::Hold) });
+        guest.send(&input);
+        guest.flush();
+        assert_eq!(receive_some(&mut connection), std::slice::from_ref(&input));
+        spectators.relay(1, &input);
+
+        // a spectator joining during the game gets its Start and everything relayed so far
+        let watcher = thread::spawn(move || {
+            let (mut watcher, watched) = spectate(&address).unwrap();
+            (receive_some(&mut watcher), watched)
+        });
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while spectators.is_empty() && Instant::now() < deadline {
+            spectators.update();
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(spectators.len(), 1);
+        spectators.update();
+        let (relayed, watched) = watcher.join().unwrap();
+        assert_eq!(watched, config);
+        assert_eq!(relayed, [Message::Relay { player: 1, message: Box::new(input) }]);
+
+        connection.close();
+        assert_eq!(receive_some(&mut guest), [Message::Bye]);
+        assert!(guest.is_closed());
+    }
+}
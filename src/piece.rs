@@ -0,0 +1,119 @@
+use crate::board::Board;
+use crate::srs::{KickTable, Rotation};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PieceKind {
+    I,
+    O,
+    #[default]
+    T,
+    S,
+    Z,
+    J,
+    L,
+}
+
+impl PieceKind {
+
+    pub const ALL: [PieceKind; 7] = [
+        PieceKind::I,
+        PieceKind::O,
+        PieceKind::T,
+        PieceKind::S,
+        PieceKind::Z,
+        PieceKind::J,
+        PieceKind::L,
+    ];
+
+    // blocks of the spawn state inside the bounding box, (0, 0) is the bottom left corner
+    fn shape(self) -> [(i32, i32); 4] {
+        match self {
+            PieceKind::I => [(0, 2), (1, 2), (2, 2), (3, 2)],
+            PieceKind::O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+            PieceKind::T => [(0, 1), (1, 1), (2, 1), (1, 2)],
+            PieceKind::S => [(0, 1), (1, 1), (1, 2), (2, 2)],
+            PieceKind::Z => [(0, 2), (1, 2), (1, 1), (2, 1)],
+            PieceKind::J => [(0, 2), (0, 1), (1, 1), (2, 1)],
+            PieceKind::L => [(0, 1), (1, 1), (2, 1), (2, 2)],
+        }
+    }
+
+    fn size(self) -> i32 {
+        match self {
+            PieceKind::I => 4,
+            PieceKind::O => 2,
+            _ => 3,
+        }
+    }
+
+    pub fn kicks(self) -> KickTable {
+        match self {
+            PieceKind::I => KickTable::I,
+            PieceKind::O => KickTable::O,
+            _ => KickTable::JLSTZ,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Piece {
+    pub kind: PieceKind,
+    pub rotation: Rotation,
+    // bottom left corner of the bounding box on the board
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Piece {
+
+    pub fn new(kind: PieceKind) -> Piece {
+        Piece {
+            kind,
+            rotation: Rotation::Spawn,
+            x: 0,
+            y: 0,
+        }
+    }
+
+    // block positions inside the bounding box for the current rotation state
+    pub fn blocks(&self) -> impl Iterator<Item = (i32, i32)> {
+        let turns = self.rotation.turns();
+        let size = self.kind.size();
+        self.kind.shape().into_iter().map(move |(x, y)| {
+            let (mut x, mut y) = (x, y);
+            for _ in 0..turns {
+                (x, y) = (y, size - 1 - x);
+            }
+            (x, y)
+        })
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.blocks().map(|(x, y)| (self.x + x, self.y + y))
+    }
+
+    pub fn moved(&self, dx: i32, dy: i32) -> Piece {
+        let mut piece = self.clone();
+        piece.x += dx;
+        piece.y += dy;
+        piece
+    }
+
+    pub fn with_rotation(&self, rotation: Rotation) -> Piece {
+        let mut piece = self.clone();
+        piece.rotation = rotation;
+        piece
+    }
+
+    // Puts the piece in its spawn state, centered and resting just above the visible field.
+    pub fn spawn(&mut self, board: &Board) {
+        self.rotation = Rotation::Spawn;
+        let min_y = self.blocks().map(|(_, y)| y).min().unwrap_or(0);
+        self.x = (board.width() as i32 - self.kind.size()) / 2;
+        self.y = board.visible_height() as i32 - min_y;
+    }
+
+    pub fn lowest_y(&self) -> i32 {
+        self.cells().map(|(_, y)| y).min().unwrap_or(self.y)
+    }
+}
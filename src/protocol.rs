@@ -0,0 +1,281 @@
+use std::io::{self, Read};
+
+use crate::config::GameConfig;
+use crate::encoding;
+use crate::game::Game;
+use crate::replay::{self, TimedInput};
+
+// The messages of a versus game over the network.
+//
+// Every player runs their own game and is the only authority on it. The other side keeps a
+// copy of that game, started from the same config and fed the Input, Garbage and Tick
+// messages in the order they were sent, so that the copy plays out exactly like the original.
+// Garbage is never sent as such: when the copy of the opponent's game attacks, the real game
+// receives the rows and reports that with a Garbage message for the opponent's copy of it.
+// The real game never waits for the network, lag only delays the copy.
+//
+// A message is a type byte followed by its fields, all numbers little endian:
+//
+//   1 Hello    u32 protocol version, u8 1 if the sender only watches, 0 if it plays,
+//              sent by both sides right after connecting
+//   2 Start    the config of the next game in the layout of replays, sent by the host
+//   3 Input    u64 tick, u8 input as in replays: the sender made the input at that tick
+//   4 Garbage  u64 tick, u32 rows: the game of the sender received the rows at that tick
+//   5 Tick     u64 tick: the game of the sender has advanced to that tick
+//   6 Rematch  the sender wants to play again once the game is over
+//   7 Ping     sent when nothing else was for a while, a silent connection is a dead one
+//   8 Bye      the sender quit
+//   9 Relay    u8 player, then a message of that player: the host forwards the Input,
+//              Garbage, Tick and End messages of both players to spectators, player 0 is the host,
+//              a Relay never contains another Relay
+//  10 End      u64 tick: the game of the sender stopped for good at that tick, because it is
+//              over or the sender saw the other game end
+//
+// The host answers the Hello of the guest with its own and a Start. After a game both sides
+// send a Rematch, and once the host has both it sends the Start of the next game.
+// Lag means a player can top out before it learns that the other one already did, so the result
+// is only decided once both Ends are in: the game that ended at the earlier tick lost.
+// Spectators get the Start of the game being played and its Relay messages so far when they
+// join, and everything after that as it happens, possibly held back by a delay.
+pub const VERSION: u32 = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Hello { version: u32, spectator: bool },
+    Start(GameConfig),
+    Input(TimedInput),
+    Garbage { tick: u64, rows: u32 },
+    Tick(u64),
+    Rematch,
+    Ping,
+    Bye,
+    Relay { player: u8, message: Box<Message> },
+    End(u64),
+}
+
+impl Message {
+
+    // Messages that advance the copy of a game, the ones `apply` uses.
+    pub fn is_game(&self) -> bool {
+        matches!(self, Message::Input(_) | Message::Garbage { .. } | Message::Tick(_) | Message::End(_))
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        // writing to a Vec can't fail
+        let _ = self.write_to(&mut bytes);
+        bytes
+    }
+
+    fn write_to(&self, bytes: &mut Vec<u8>) -> io::Result<()> {
+        match self {
+            Message::Hello { version, spectator } => {
+                bytes.push(1);
+                encoding::write_u32(bytes, *version)?;
+                bytes.push(*spectator as u8);
+            }
+            Message::Start(config) => {
+                bytes.push(2);
+                replay::write_config(bytes, config)?;
+            }
+            Message::Input(input) => {
+                bytes.push(3);
+                encoding::write_u64(bytes, input.tick)?;
+                bytes.push(replay::input_to_byte(input.input));
+            }
+            Message::Garbage { tick, rows } => {
+                bytes.push(4);
+                encoding::write_u64(bytes, *tick)?;
+                encoding::write_u32(bytes, *rows)?;
+            }
+            Message::Tick(tick) => {
+                bytes.push(5);
+                encoding::write_u64(bytes, *tick)?;
+            }
+            Message::Rematch => bytes.push(6),
+            Message::Ping => bytes.push(7),
+            Message::Bye => bytes.push(8),
+            Message::Relay { player, message } => {
+                bytes.push(9);
+                bytes.push(*player);
+                message.write_to(bytes)?;
+            }
+            Message::End(tick) => {
+                bytes.push(10);
+                encoding::write_u64(bytes, *tick)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Reads the first message of `bytes`, returns it and its length in bytes,
+    // or None if the message isn't complete yet.
+    pub fn decode(bytes: &[u8]) -> io::Result<Option<(Message, usize)>> {
+        let mut reader = bytes;
+        match Message::read_from(&mut reader) {
+            Ok(message) => Ok(Some((message, bytes.len() - reader.len()))),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn read_from(reader: &mut impl Read) -> io::Result<Message> {
+        let mut kind = [0u8; 1];
+        reader.read_exact(&mut kind)?;
+        Message::read_fields(kind[0], reader)
+    }
+
+    fn read_fields(kind: u8, reader: &mut impl Read) -> io::Result<Message> {
+        Ok(match kind {
+            1 => {
+                let version = encoding::read_u32(reader)?;
+                let mut spectator = [0u8; 1];
+                reader.read_exact(&mut spectator)?;
+                Message::Hello { version, spectator: spectator[0] != 0 }
+            }
+            2 => Message::Start(replay::read_config(reader)?),
+            3 => {
+                let tick = encoding::read_u64(reader)?;
+                let mut input = [0u8; 1];
+                reader.read_exact(&mut input)?;
+                Message::Input(TimedInput { tick, input: replay::input_from_byte(input[0])? })
+            }
+            4 => Message::Garbage { tick: encoding::read_u64(reader)?, rows: encoding::read_u32(reader)? },
+            5 => Message::Tick(encoding::read_u64(reader)?),
+            6 => Message::Rematch,
+            7 => Message::Ping,
+            8 => Message::Bye,
+            9 => {
+                let mut player = [0u8; 1];
+                reader.read_exact(&mut player)?;
+                let mut kind = [0u8; 1];
+                reader.read_exact(&mut kind)?;
+                // only the messages of players are relayed, nested relays could exhaust the stack
+                if kind[0] == 9 {
+                    return Err(encoding::invalid("a relay can't contain another relay"));
+                }
+                Message::Relay { player: player[0], message: Box::new(Message::read_fields(kind[0], reader)?) }
+            }
+            10 => Message::End(encoding::read_u64(reader)?),
+            kind => return Err(encoding::invalid(format!("unknown message type {kind}"))),
+        })
+    }
+}
+
+// Brings the copy of a player's game up to date with a message of that player.
+// Messages that aren't about the game are ignored.
+pub fn apply(game: &mut Game, message: &Message) {
+    match message {
+        Message::Input(input) => {
+            advance(game, input.tick);
+            game.input(input.input);
+        }
+        Message::Garbage { tick, rows } => {
+            advance(game, *tick);
+            game.receive_garbage(*rows);
+        }
+        Message::Tick(tick) | Message::End(tick) => advance(game, *tick),
+        _ => {}
+    }
+}
+
+fn advance(game: &mut Game, tick: u64) {
+    while game.ticks() < tick && !game.is_over() {
+        game.tick();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Cell;
+    use crate::input::{Action, Input};
+    use crate::mode::GameMode;
+
+    fn messages() -> Vec<Message> {
+        let input = TimedInput { tick: 12, input: Input::Release(Action::Rotate180) };
+        vec![
+            Message::Hello { version: VERSION, spectator: true },
+            Message::Start(GameConfig { seed: 99, mode: GameMode::Versus, ..GameConfig::default() }),
+            Message::Input(input),
+            Message::Garbage { tick: 40, rows: 3 },
+            Message::Tick(u64::MAX),
+            Message::Rematch,
+            Message::Ping,
+            Message::Bye,
+            Message::Relay { player: 1, message: Box::new(Message::Input(input)) },
+            Message::Relay { player: 0, message: Box::new(Message::End(7)) },
+            Message::End(300),
+        ]
+    }
+
+    #[test]
+    fn messages_decode_to_what_was_encoded() {
+        for message in messages() {
+            let bytes = message.encode();
+            assert_eq!(Message::decode(&bytes).unwrap(), Some((message, bytes.len())));
+        }
+    }
+
+    #[test]
+    fn incomplete_messages_wait_for_more_bytes() {
+        let stream: Vec<u8> = messages().iter().flat_map(Message::encode).collect();
+        let mut decoded = vec![];
+        let mut start = 0;
+        // the bytes trickle in one at a time
+        for end in 1..=stream.len() {
+            if let Some((message, len)) = Message::decode(&stream[start..end]).unwrap() {
+                assert_eq!(start + len, end);
+                decoded.push(message);
+                start = end;
+            }
+        }
+        assert_eq!(decoded, messages());
+        assert!(Message::decode(&[42]).is_err());
+    }
+
+    #[test]
+    fn relays_inside_relays_are_rejected() {
+        let nested = Message::Relay { player: 0, message: Box::new(Message::Relay { player: 1, message: Box::new(Message::Ping) }) };
+        let error = Message::decode(&nested.encode()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        // deep nesting is turned down right away instead of overflowing the stack
+        let flood: Vec<u8> = [9, 0].repeat(1 << 20);
+        let error = Message::decode(&flood).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn applied_messages_keep_the_copy_in_step() {
+        let config = GameConfig { seed: 5, mode: GameMode::Versus, ..GameConfig::default() };
+        let mut game = Game::new(&config);
+        let mut copy = Game::new(&config);
+        let mut sent = vec![];
+        let moves = [Action::Left, Action::RotateClockwise, Action::Right, Action::HardDrop];
+        for tick in 0..600u64 {
+            if tick % 10 == 0 {
+                let action = moves[(tick / 10) as usize % moves.len()];
+                for input in [Input::Press(action), Input::Release(action)] {
+                    game.input(input);
+                    sent.push(Message::Input(TimedInput { tick, input }));
+                }
+            }
+            if tick % 150 == 75 {
+                game.receive_garbage(2);
+                sent.push(Message::Garbage { tick, rows: 2 });
+            }
+            game.tick();
+            sent.push(Message::Tick(game.ticks()));
+        }
+        sent.push(Message::End(game.ticks()));
+        for message in &sent {
+            apply(&mut copy, message);
+        }
+        assert!(game.board().occupied().any(|(_, _, cell)| cell == Cell::Garbage));
+        assert_eq!(copy.ticks(), game.ticks());
+        assert_eq!(copy.board(), game.board());
+        assert_eq!(copy.score(), game.score());
+        assert_eq!(copy.current_piece(), game.current_piece());
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::str::FromStr;
+
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::piece::PieceKind;
+
+// ChaCha8 produces the same sequence for a seed on every platform, unlike the OS or thread rngs.
+pub type GameRng = ChaCha8Rng;
+
+pub fn seeded_rng(seed: u64) -> GameRng {
+    GameRng::seed_from_u64(seed)
+}
+
+pub trait Randomizer: Debug {
+    fn next(&mut self) -> PieceKind;
+    // a copy in the same state, so that a copied game deals the same pieces
+    fn clone_box(&self) -> Box<dyn Randomizer>;
+}
+
+impl Clone for Box<dyn Randomizer> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RandomizerKind {
+    #[default]
+    SevenBag,
+    FourteenBag,
+    Random,
+    History,
+}
+
+impl RandomizerKind {
+
+    pub fn build(self, seed: u64) -> Box<dyn Randomizer> {
+        let rng = seeded_rng(seed);
+        match self {
+            RandomizerKind::SevenBag => Box::new(BagRandomizer::new(1, rng)),
+            RandomizerKind::FourteenBag => Box::new(BagRandomizer::new(2, rng)),
+            RandomizerKind::Random => Box::new(PureRandomizer::new(rng)),
+            RandomizerKind::History => Box::new(HistoryRandomizer::new(4, rng)),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RandomizerKind::SevenBag => "7bag",
+            RandomizerKind::FourteenBag => "14bag",
+            RandomizerKind::Random => "random",
+            RandomizerKind::History => "history",
+        }
+    }
+}
+
+impl FromStr for RandomizerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "7bag" | "bag" => Ok(RandomizerKind::SevenBag),
+            "14bag" => Ok(RandomizerKind::FourteenBag),
+            "random" => Ok(RandomizerKind::Random),
+            "history" | "tgm" => Ok(RandomizerKind::History),
+            _ => Err(format!("unknown randomizer {s:?}, expected one of 7bag, 14bag, random, history")),
+        }
+    }
+}
+
+// Deals every piece `copies` times in random order before refilling.
+#[derive(Debug, Clone)]
+pub struct BagRandomizer {
+    bag: Vec<PieceKind>,
+    copies: usize,
+    rng: GameRng,
+}
+
+impl BagRandomizer {
+
+    pub fn new(copies: usize, rng: GameRng) -> BagRandomizer {
+        BagRandomizer {
+            bag: vec![],
+            copies,
+            rng,
+        }
+    }
+}
+
+impl Randomizer for BagRandomizer {
+    fn next(&mut self) -> PieceKind {
+        if self.bag.is_empty() {
+            for _ in 0..self.copies {
+                self.bag.extend_from_slice(&PieceKind::ALL);
+            }
+            self.bag.shuffle(&mut self.rng);
+        }
+        self.bag.pop().unwrap()
+    }
+
+    fn clone_box(&self) -> Box<dyn Randomizer> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PureRandomizer {
+    rng: GameRng,
+}
+
+impl PureRandomizer {
+
+    pub fn new(rng: GameRng) -> PureRandomizer {
+        PureRandomizer {
+            rng,
+        }
+    }
+}
+
+impl Randomizer for PureRandomizer {
+    fn next(&mut self) -> PieceKind {
+        random_kind(&mut self.rng)
+    }
+
+    fn clone_box(&self) -> Box<dyn Randomizer> {
+        Box::new(self.clone())
+    }
+}
+
+// u32 ranges sample the same way on 32 and 64 bit platforms, usize ranges don't
+fn random_kind(rng: &mut GameRng) -> PieceKind {
+    PieceKind::ALL[rng.gen_range(0..PieceKind::ALL.len() as u32) as usize]
+}
+
+// TGM style: rerolls up to `rolls` times while the piece is in the history of the last four pieces.
+#[derive(Debug, Clone)]
+pub struct HistoryRandomizer {
+    history: VecDeque<PieceKind>,
+    rolls: usize,
+    first: bool,
+    rng: GameRng,
+}
+
+impl HistoryRandomizer {
+
+    pub fn new(rolls: usize, rng: GameRng) -> HistoryRandomizer {
+        HistoryRandomizer {
+            history: VecDeque::from([PieceKind::Z; 4]),
+            rolls,
+            first: true,
+            rng,
+        }
+    }
+}
+
+impl Randomizer for HistoryRandomizer {
+    fn next(&mut self) -> PieceKind {
+        let kind = if self.first {
+            // the first piece is never an S, Z or O
+            self.first = false;
+            [PieceKind::I, PieceKind::T, PieceKind::J, PieceKind::L][self.rng.gen_range(0..4u32) as usize]
+        }
+        else {
+            let mut kind = random_kind(&mut self.rng);
+            for _ in 1..self.rolls {
+                if !self.history.contains(&kind) {
+                    break;
+                }
+                kind = random_kind(&mut self.rng);
+            }
+            kind
+        };
+        self.history.pop_front();
+        self.history.push_back(kind);
+        kind
+    }
+
+    fn clone_box(&self) -> Box<dyn Randomizer> {
+        Box::new(self.clone())
+    }
+}
@@ -1,17 +1,237 @@
+use std::env;
+use std::ffi::OsString;
 use std::io;
 use std::io::prelude::*;
-use std::path::Path;
-use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::fs::{self, File};
+
+use tetris::encoding::invalid;
+use tetris::replay::Replay;
+
+// Save files are the magic, the format version, the payload length, the payload
+// and a checksum of everything before it, all numbers little endian.
+const MAGIC: &[u8; 4] = b"TTRS";
+pub const VERSION: u32 = 1;
+// size of the header in front of the payload and of the checksum after it
+const HEADER_LEN: usize = 12;
+const CHECKSUM_LEN: usize = 4;
+
+// older versions wrote the bare score to this file in the current directory
+const LEGACY_HIGHSCORE_PATH: &str = "Highscore.bin";
+
+// $XDG_DATA_HOME/tetris, or ~/.local/share/tetris if that isn't set.
+pub fn data_dir() -> io::Result<PathBuf> {
+    let base = match env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let home = env::var_os("HOME")
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "neither XDG_DATA_HOME nor HOME is set"))?;
+            PathBuf::from(home).join(".local").join("share")
+        }
+    };
+    let dir = base.join("tetris");
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+pub fn data_path(name: &str) -> io::Result<PathBuf> {
+    Ok(data_dir()?.join(name))
+}
+
+pub fn highscore_path() -> io::Result<PathBuf> {
+    data_path("highscore.bin")
+}
+
+pub fn leaderboard_path() -> io::Result<PathBuf> {
+    data_path("leaderboard.bin")
+}
+
+pub fn personal_bests_path() -> io::Result<PathBuf> {
+    data_path("personal_bests.bin")
+}
+
+pub fn replay_path() -> io::Result<PathBuf> {
+    data_path("replay.bin")
+}
 
 pub fn save(path: &Path, number: u64) -> io::Result<()> {
-    let mut file = File::create(path)?;
-    file.write_all(&number.to_le_bytes())?;
-    Ok(())
+    save_data(path, &number.to_le_bytes())
 }
 
+// Reads the score saved at `path`. A missing file is a score of 0. Unreadable files are
+// moved aside to a .bak file and reset, bare 8 byte scores of older versions are kept.
 pub fn read(path: &Path) -> io::Result<u64> {
-    let mut file = File::open(path)?;
-    let mut buffer = [0u8; 8];
-    file.read_exact(&mut buffer)?;
-    Ok(u64::from_le_bytes(buffer))
-}
\ No newline at end of file
+    if !path.exists() {
+        return read_legacy(Path::new(LEGACY_HIGHSCORE_PATH));
+    }
+    let bytes = fs::read(path)?;
+    if bytes.len() == 8 {
+        backup(path)?;
+        return Ok(legacy_score(&bytes));
+    }
+    match decode(&bytes).and_then(|payload| payload.try_into().map_err(|_| invalid("wrong payload size"))) {
+        Ok(payload) => Ok(u64::from_le_bytes(payload)),
+        Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+            backup(path)?;
+            Ok(0)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+fn read_legacy(path: &Path) -> io::Result<u64> {
+    match fs::read(path) {
+        Ok(bytes) if bytes.len() == 8 => Ok(legacy_score(&bytes)),
+        Ok(_) => Ok(0),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(0),
+        Err(err) => Err(err),
+    }
+}
+
+fn legacy_score(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes.try_into().unwrap())
+}
+
+// Writes `payload` with a header and checksum. The data goes to a temporary file first
+// which then replaces the old file, so a crash never leaves a half written save behind.
+pub fn save_data(path: &Path, payload: &[u8]) -> io::Result<()> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len() + CHECKSUM_LEN);
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(payload);
+    bytes.extend_from_slice(&checksum(&bytes).to_le_bytes());
+
+    let temp = with_suffix(path, ".tmp");
+    let mut file = File::create(&temp)?;
+    file.write_all(&bytes)?;
+    file.sync_all()?;
+    fs::rename(&temp, path)
+}
+
+// Returns the payload of a file written by `save_data`, InvalidData if it is damaged.
+pub fn read_data(path: &Path) -> io::Result<Vec<u8>> {
+    decode(&fs::read(path)?).map(|payload| payload.to_vec())
+}
+
+// Like `read_data`, but None if there is no file yet or it was damaged and moved aside.
+pub fn load_data(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match read_data(path) {
+        Ok(payload) => Ok(Some(payload)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+            backup(path)?;
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+fn decode(bytes: &[u8]) -> io::Result<&[u8]> {
+    if bytes.len() < HEADER_LEN + CHECKSUM_LEN || &bytes[..4] != MAGIC {
+        return Err(invalid("not a save file"));
+    }
+    let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+    if version != VERSION {
+        return Err(invalid("unsupported save file version"));
+    }
+    let len = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize;
+    if bytes.len() != HEADER_LEN + len + CHECKSUM_LEN {
+        return Err(invalid("save file has the wrong length"));
+    }
+    let (data, stored) = bytes.split_at(HEADER_LEN + len);
+    if checksum(data) != u32::from_le_bytes(stored.try_into().unwrap()) {
+        return Err(invalid("save file checksum mismatch"));
+    }
+    Ok(&data[HEADER_LEN..])
+}
+
+// Keeps a copy of a damaged file as <name>.bak before it gets overwritten.
+pub fn backup(path: &Path) -> io::Result<()> {
+    fs::rename(path, with_suffix(path, ".bak"))
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+// 32 bit FNV-1a, enough to notice truncated or garbled files.
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5, |hash, byte| (hash ^ *byte as u32).wrapping_mul(0x01000193))
+}
+
+pub fn save_replay(path: &Path, replay: &Replay) -> io::Result<()> {
+    let mut file = io::BufWriter::new(File::create(path)?);
+    replay.write_to(&mut file)?;
+    file.flush()
+}
+
+pub fn read_replay(path: &Path) -> io::Result<Replay> {
+    Replay::read_from(io::BufReader::new(File::open(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An empty directory only used by the test called `name`.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("tetris-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn saved_scores_read_back() {
+        let dir = temp_dir("saved");
+        let path = dir.join("highscore.bin");
+        save(&path, 1234).unwrap();
+        assert_eq!(read(&path).unwrap(), 1234);
+        assert!(!with_suffix(&path, ".tmp").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn missing_files_are_empty() {
+        let dir = temp_dir("missing");
+        let path = dir.join("highscore.bin");
+        assert_eq!(load_data(&path).unwrap(), None);
+        assert_eq!(read_legacy(&path).unwrap(), 0);
+        assert!(!path.exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn damaged_files_are_backed_up_and_reset() {
+        let dir = temp_dir("damaged");
+        let path = dir.join("highscore.bin");
+        save(&path, 99).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        let mut wrong_checksum = bytes.clone();
+        *wrong_checksum.last_mut().unwrap() ^= 1;
+        for damaged in [vec![], bytes[..bytes.len() - 3].to_vec(), wrong_checksum] {
+            fs::write(&path, &damaged).unwrap();
+            assert_eq!(read(&path).unwrap(), 0);
+            assert!(!path.exists());
+            assert_eq!(fs::read(with_suffix(&path, ".bak")).unwrap(), damaged);
+
+            fs::write(&path, &damaged).unwrap();
+            assert_eq!(load_data(&path).unwrap(), None);
+            assert!(!path.exists());
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn legacy_scores_are_kept() {
+        let dir = temp_dir("legacy");
+        let path = dir.join("highscore.bin");
+        fs::write(&path, 5000u64.to_le_bytes()).unwrap();
+        assert_eq!(read(&path).unwrap(), 5000);
+        assert_eq!(fs::read(with_suffix(&path, ".bak")).unwrap(), 5000u64.to_le_bytes());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
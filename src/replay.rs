@@ -0,0 +1,286 @@
+use std::io::{self, Read, Write};
+
+use crate::config::GameConfig;
+use crate::encoding::{invalid, read_str, read_u32, read_u64, write_str, write_u32, write_u64};
+use crate::game::Game;
+use crate::input::{Action, Input};
+
+const MAGIC: &[u8; 4] = b"TRPL";
+// bump whenever the layout below or the behaviour of the engine changes
+pub const VERSION: u32 = 5;
+
+// An input together with the tick it was made on, it is applied before that tick advances.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimedInput {
+    pub tick: u64,
+    pub input: Input,
+}
+
+// Everything needed to play a game again: the rules with the seed and every input in order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub config: GameConfig,
+    pub inputs: Vec<TimedInput>,
+    // number of ticks the game ran for
+    pub ticks: u64,
+}
+
+impl Replay {
+
+    pub fn new(config: GameConfig) -> Replay {
+        Replay {
+            config,
+            inputs: vec![],
+            ticks: 0,
+        }
+    }
+
+    // Records an input made while the game was at `tick`.
+    pub fn record(&mut self, tick: u64, input: Input) {
+        self.inputs.push(TimedInput { tick, input });
+        self.ticks = self.ticks.max(tick);
+    }
+
+    pub fn finish(&mut self, ticks: u64) {
+        self.ticks = self.ticks.max(ticks);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    // Layout, all numbers little endian: magic, version, the config with the mode and randomizer
+    // as length prefixed names, ticks, input count,
+    // then per input the tick and one byte with the action index, the high bit set for releases.
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        write_u32(&mut writer, VERSION)?;
+        write_config(&mut writer, &self.config)?;
+        write_u64(&mut writer, self.ticks)?;
+        write_u32(&mut writer, self.inputs.len() as u32)?;
+        for input in &self.inputs {
+            write_u64(&mut writer, input.tick)?;
+            writer.write_all(&[input_to_byte(input.input)])?;
+        }
+        Ok(())
+    }
+
+    pub fn read_from(mut reader: impl Read) -> io::Result<Replay> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a replay file"));
+        }
+        let version = read_u32(&mut reader)?;
+        if version != VERSION {
+            return Err(invalid(format!("unsupported replay version {version}, expected {VERSION}")));
+        }
+        let config = read_config(&mut reader)?;
+        let ticks = read_u64(&mut reader)?;
+        let count = read_u32(&mut reader)?;
+        let mut inputs = vec![];
+        for _ in 0..count {
+            let tick = read_u64(&mut reader)?;
+            let mut byte = [0u8; 1];
+            reader.read_exact(&mut byte)?;
+            inputs.push(TimedInput { tick, input: input_from_byte(byte[0])? });
+        }
+        Ok(Replay { config, inputs, ticks })
+    }
+}
+
+// Feeds the inputs of a replay into a game tick by tick.
+#[derive(Debug, Clone)]
+pub struct Playback {
+    replay: Replay,
+    // index of the next input to apply
+    next: usize,
+}
+
+impl Playback {
+
+    pub fn new(replay: Replay) -> Playback {
+        Playback {
+            replay,
+            next: 0,
+        }
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    // A new game at the start of the replay.
+    pub fn restart(&mut self) -> Game {
+        self.next = 0;
+        Game::new(&self.replay.config)
+    }
+
+    pub fn is_finished(&self, game: &Game) -> bool {
+        self.next >= self.replay.inputs.len() && (game.ticks() >= self.replay.ticks || game.is_over())
+    }
+
+    // Applies the inputs of the current tick and advances the game by one tick.
+    // Returns false once the replay is over.
+    pub fn step(&mut self, game: &mut Game) -> bool {
+        while let Some(input) = self.replay.inputs.get(self.next) {
+            if input.tick > game.ticks() {
+                break;
+            }
+            game.input(input.input);
+            self.next += 1;
+        }
+        if self.is_finished(game) {
+            return false;
+        }
+        game.tick();
+        true
+    }
+
+    // Moves to `tick`, going back means playing the game again from the start.
+    pub fn seek(&mut self, game: &mut Game, tick: u64) {
+        if tick < game.ticks() {
+            *game = self.restart();
+        }
+        while game.ticks() < tick && self.step(game) {}
+    }
+}
+
+// The seed, the mode and randomizer as length prefixed names, then the numbers of the config.
+// Shared with the network protocol.
+pub(crate) fn write_config(writer: &mut impl Write, config: &GameConfig) -> io::Result<()> {
+    write_u64(writer, config.seed)?;
+    write_str(writer, config.mode.name())?;
+    write_str(writer, config.randomizer.name())?;
+    for value in [
+        config.soft_drop_factor,
+        config.lock_delay_ms,
+        config.lock_resets,
+        config.das_ms,
+        config.arr_ms,
+        config.start_level,
+        config.lines_per_level,
+        config.queue_size as u32,
+        config.time_limit_ms,
+        config.garbage_rows,
+        config.garbage_interval_ms,
+        config.garbage_delay_ms,
+        config.garbage_messiness,
+    ] {
+        write_u32(writer, value)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn read_config(reader: &mut impl Read) -> io::Result<GameConfig> {
+    let seed = read_u64(reader)?;
+    let mode = read_str(reader)?.parse().map_err(invalid)?;
+    let randomizer = read_str(reader)?.parse().map_err(invalid)?;
+    Ok(GameConfig {
+        seed,
+        mode,
+        randomizer,
+        soft_drop_factor: read_u32(reader)?,
+        lock_delay_ms: read_u32(reader)?,
+        lock_resets: read_u32(reader)?,
+        das_ms: read_u32(reader)?,
+        arr_ms: read_u32(reader)?,
+        start_level: read_u32(reader)?,
+        lines_per_level: read_u32(reader)?,
+        queue_size: read_u32(reader)? as usize,
+        time_limit_ms: read_u32(reader)?,
+        garbage_rows: read_u32(reader)?,
+        garbage_interval_ms: read_u32(reader)?,
+        garbage_delay_ms: read_u32(reader)?,
+        garbage_messiness: read_u32(reader)?,
+    })
+}
+
+// The action index, with the high bit set for releases.
+pub(crate) fn input_to_byte(input: Input) -> u8 {
+    match input {
+        Input::Press(action) => action.index() as u8,
+        Input::Release(action) => action.index() as u8 | 0x80,
+    }
+}
+
+pub(crate) fn input_from_byte(byte: u8) -> io::Result<Input> {
+    let action = Action::ALL.get((byte & 0x7f) as usize).copied()
+        .ok_or_else(|| invalid(format!("unknown action {}", byte & 0x7f)))?;
+    Ok(if byte & 0x80 == 0 { Input::Press(action) } else { Input::Release(action) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Plays a game with a few pieces while recording it, returns the game and its replay.
+    fn record() -> (Game, Replay) {
+        let config = GameConfig { seed: 3, das_ms: 120, garbage_rows: 2, ..GameConfig::default() };
+        let mut game = Game::new(&config);
+        let mut replay = Replay::new(config);
+        let moves = [Action::Left, Action::Right, Action::RotateClockwise, Action::Rotate180];
+        for piece in 0..12 {
+            let action = moves[piece % moves.len()];
+            for input in [Input::Press(action), Input::Release(action), Input::Press(Action::HardDrop)] {
+                replay.record(game.ticks(), input);
+                game.input(input);
+                for _ in 0..5 {
+                    game.tick();
+                }
+            }
+        }
+        replay.finish(game.ticks());
+        (game, replay)
+    }
+
+    fn round_trip(replay: &Replay) -> io::Result<Replay> {
+        let mut bytes = vec![];
+        replay.write_to(&mut bytes)?;
+        Replay::read_from(bytes.as_slice())
+    }
+
+    #[test]
+    fn playback_of_a_saved_replay_ends_on_the_recorded_board() {
+        let (recorded, replay) = record();
+        assert!(recorded.pieces() >= 12);
+        let loaded = round_trip(&replay).unwrap();
+        assert_eq!(loaded, replay);
+
+        let mut playback = Playback::new(loaded);
+        let mut game = playback.restart();
+        while playback.step(&mut game) {}
+        assert!(playback.is_finished(&game));
+        assert_eq!(game.ticks(), recorded.ticks());
+        assert_eq!(game.board(), recorded.board());
+        assert_eq!(game.score(), recorded.score());
+
+        // seeking back plays the game again from the start
+        playback.seek(&mut game, 10);
+        assert_eq!(game.ticks(), 10);
+        playback.seek(&mut game, replay.ticks);
+        assert_eq!(game.board(), recorded.board());
+    }
+
+    #[test]
+    fn bad_files_are_rejected() {
+        let (_, replay) = record();
+        let mut bytes = vec![];
+        replay.write_to(&mut bytes).unwrap();
+
+        let mut magic = bytes.clone();
+        magic[0] = b'X';
+        let error = Replay::read_from(magic.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let mut version = bytes.clone();
+        version[4..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        let error = Replay::read_from(version.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        for len in [0, 3, 10, bytes.len() - 1] {
+            let error = Replay::read_from(&bytes[..len]).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        }
+    }
+}
@@ -0,0 +1,316 @@
+use crate::board::Board;
+use crate::piece::{Piece, PieceKind};
+use crate::srs::{Rotation, RotationDirection};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TSpin {
+    #[default]
+    None,
+    Mini,
+    Full,
+}
+
+// Recognises T-spins with the 3-corner rule. `kick` is the direction and kick index of the rotation
+// that brought the piece into place, None if its last successful action was not a rotation.
+pub fn detect_t_spin(board: &Board, piece: &Piece, kick: Option<(RotationDirection, usize)>) -> TSpin {
+    let Some((direction, kick)) = kick else {
+        return TSpin::None;
+    };
+    if piece.kind != PieceKind::T {
+        return TSpin::None;
+    }
+    let occupied = |(x, y): (i32, i32)| !board.is_free(piece.x + x, piece.y + y);
+    let corners = [(0, 0), (2, 0), (0, 2), (2, 2)];
+    if corners.into_iter().filter(|corner| occupied(*corner)).count() < 3 {
+        return TSpin::None;
+    }
+    // the two corners next to the side the T is pointing at
+    let front = match piece.rotation {
+        Rotation::Spawn => [(0, 2), (2, 2)],
+        Rotation::Right => [(2, 2), (2, 0)],
+        Rotation::Reverse => [(0, 0), (2, 0)],
+        Rotation::Left => [(0, 0), (0, 2)],
+    };
+    // the last kick of the 90° tables always counts as a full T-spin, in the 180° table it is a plain shift
+    let last_kick = kick == 4 && direction != RotationDirection::Half;
+    if front.into_iter().all(occupied) || last_kick {
+        TSpin::Full
+    }
+    else {
+        TSpin::Mini
+    }
+}
+
+// A scored lock, with everything the UI needs to describe it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoreAction {
+    pub lines: u32,
+    pub t_spin: TSpin,
+    // number of consecutive line clears before this one
+    pub combo: u32,
+    pub back_to_back: bool,
+    pub perfect_clear: bool,
+    pub points: u64,
+}
+
+impl ScoreAction {
+
+    // Text to flash in the UI, e.g. "B2B T-SPIN DOUBLE".
+    pub fn describe(&self) -> String {
+        let mut parts = vec![];
+        if self.back_to_back {
+            parts.push("B2B".to_string());
+        }
+        match self.t_spin {
+            TSpin::Full => parts.push("T-SPIN".to_string()),
+            TSpin::Mini => parts.push("T-SPIN MINI".to_string()),
+            TSpin::None => {}
+        }
+        match self.lines {
+            0 => {}
+            1 => parts.push("SINGLE".to_string()),
+            2 => parts.push("DOUBLE".to_string()),
+            3 => parts.push("TRIPLE".to_string()),
+            _ => parts.push("TETRIS".to_string()),
+        }
+        if self.combo > 0 {
+            parts.push(format!("COMBO {}", self.combo));
+        }
+        if self.perfect_clear {
+            parts.push("PERFECT CLEAR".to_string());
+        }
+        parts.join(" ")
+    }
+
+    // Tetrises and T-spins that clear lines keep a back-to-back chain going.
+    pub fn is_difficult(&self) -> bool {
+        self.lines >= 4 || (self.lines > 0 && self.t_spin != TSpin::None)
+    }
+}
+
+// How often each kind of scoring action happened in a game.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ScoreStats {
+    pub singles: u32,
+    pub doubles: u32,
+    pub triples: u32,
+    pub tetrises: u32,
+    pub t_spins: u32,
+    pub t_spin_minis: u32,
+    pub back_to_backs: u32,
+    pub perfect_clears: u32,
+    pub max_combo: u32,
+}
+
+impl ScoreStats {
+
+    pub fn add(&mut self, action: &ScoreAction) {
+        match action.t_spin {
+            TSpin::Full => self.t_spins += 1,
+            TSpin::Mini => self.t_spin_minis += 1,
+            TSpin::None => match action.lines {
+                0 => {}
+                1 => self.singles += 1,
+                2 => self.doubles += 1,
+                3 => self.triples += 1,
+                _ => self.tetrises += 1,
+            },
+        }
+        if action.back_to_back {
+            self.back_to_backs += 1;
+        }
+        if action.perfect_clear {
+            self.perfect_clears += 1;
+        }
+        self.max_combo = self.max_combo.max(action.combo);
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Scoring {
+    // consecutive line clears, None after a lock that cleared nothing
+    combo: Option<u32>,
+    back_to_back: bool,
+}
+
+impl Scoring {
+
+    pub fn new() -> Scoring {
+        Scoring::default()
+    }
+
+    // Scores a locked piece. Returns None if the lock was not worth any points.
+    pub fn lock(&mut self, lines: u32, t_spin: TSpin, perfect_clear: bool, level: u32) -> Option<ScoreAction> {
+        if lines == 0 {
+            self.combo = None;
+            if t_spin == TSpin::None {
+                return None;
+            }
+        }
+        else {
+            self.combo = Some(self.combo.map_or(0, |combo| combo + 1));
+        }
+
+        let mut action = ScoreAction {
+            lines,
+            t_spin,
+            combo: self.combo.unwrap_or(0),
+            back_to_back: false,
+            perfect_clear,
+            points: 0,
+        };
+        let level = level.max(1) as u64;
+        let mut points = base_points(lines, t_spin);
+        if action.is_difficult() {
+            action.back_to_back = self.back_to_back;
+            if self.back_to_back {
+                points += points / 2;
+            }
+            self.back_to_back = true;
+        }
+        else if lines > 0 {
+            self.back_to_back = false;
+        }
+        points += 50 * action.combo as u64;
+        if perfect_clear {
+            points += match lines {
+                1 => 800,
+                2 => 1200,
+                3 => 1800,
+                _ if action.back_to_back => 3200,
+                _ => 2000,
+            };
+        }
+        action.points = points * level;
+        Some(action)
+    }
+}
+
+fn base_points(lines: u32, t_spin: TSpin) -> u64 {
+    match (t_spin, lines) {
+        (TSpin::None, 0) => 0,
+        (TSpin::None, 1) => 100,
+        (TSpin::None, 2) => 300,
+        (TSpin::None, 3) => 500,
+        (TSpin::None, _) => 800,
+        (TSpin::Mini, 0) => 100,
+        (TSpin::Mini, 1) => 200,
+        (TSpin::Mini, _) => 400,
+        (TSpin::Full, 0) => 400,
+        (TSpin::Full, 1) => 800,
+        (TSpin::Full, 2) => 1200,
+        (TSpin::Full, _) => 1600,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Cell;
+
+    fn board(filled: &[(i32, i32)]) -> Board {
+        let mut board = Board::default();
+        for &(x, y) in filled {
+            board.set(x, y, Some(Cell::Garbage));
+        }
+        board
+    }
+
+    fn t(rotation: Rotation, x: i32, y: i32) -> Piece {
+        Piece { kind: PieceKind::T, rotation, x, y }
+    }
+
+    const ROTATED: Option<(RotationDirection, usize)> = Some((RotationDirection::Clockwise, 0));
+
+    #[test]
+    fn t_spin_double() {
+        // a T pointing down into a slot two rows deep, with an overhang over its left arm
+        let mut filled: Vec<(i32, i32)> = (0..10).filter(|x| *x != 4).map(|x| (x, 0)).collect();
+        filled.extend((0..10).filter(|x| !(3..=5).contains(x)).map(|x| (x, 1)));
+        filled.push((3, 2));
+        let board = board(&filled);
+        let piece = t(Rotation::Reverse, 3, 0);
+        assert!(board.fits(piece.cells()));
+        assert_eq!(detect_t_spin(&board, &piece, ROTATED), TSpin::Full);
+        assert_eq!(detect_t_spin(&board, &piece, None), TSpin::None);
+
+        let action = Scoring::new().lock(2, TSpin::Full, false, 1).unwrap();
+        assert_eq!(action.points, 1200);
+        assert_eq!(action.describe(), "T-SPIN DOUBLE");
+    }
+
+    // A T pointing right against the left wall, only one of the corners it points at is taken.
+    fn mini_slot() -> (Board, Piece) {
+        (board(&[(1, 0)]), t(Rotation::Right, -1, 0))
+    }
+
+    #[test]
+    fn t_spin_mini() {
+        let (board, piece) = mini_slot();
+        assert!(board.fits(piece.cells()));
+        assert_eq!(detect_t_spin(&board, &piece, ROTATED), TSpin::Mini);
+        // two corners are not enough
+        assert_eq!(detect_t_spin(&Board::default(), &piece, ROTATED), TSpin::None);
+        let action = Scoring::new().lock(0, TSpin::Mini, false, 1).unwrap();
+        assert_eq!(action.points, 100);
+    }
+
+    #[test]
+    fn only_the_last_kick_of_a_quarter_turn_makes_a_mini_full() {
+        let (board, piece) = mini_slot();
+        for direction in [RotationDirection::Clockwise, RotationDirection::CounterClockwise] {
+            assert_eq!(detect_t_spin(&board, &piece, Some((direction, 4))), TSpin::Full);
+            assert_eq!(detect_t_spin(&board, &piece, Some((direction, 3))), TSpin::Mini);
+        }
+        assert_eq!(detect_t_spin(&board, &piece, Some((RotationDirection::Half, 4))), TSpin::Mini);
+    }
+
+    #[test]
+    fn back_to_back_chains_until_a_plain_clear() {
+        let mut scoring = Scoring::new();
+        let first = scoring.lock(4, TSpin::None, false, 1).unwrap();
+        assert!(!first.back_to_back);
+        assert_eq!(first.points, 800);
+        // a lock that clears nothing keeps the chain but ends the combo
+        assert_eq!(scoring.lock(0, TSpin::None, false, 1), None);
+        let second = scoring.lock(1, TSpin::Full, false, 1).unwrap();
+        assert!(second.back_to_back);
+        assert_eq!(second.points, 800 + 400);
+        scoring.lock(0, TSpin::None, false, 1);
+        let single = scoring.lock(1, TSpin::None, false, 1).unwrap();
+        assert!(!single.back_to_back);
+        assert_eq!(single.points, 100);
+        scoring.lock(0, TSpin::None, false, 1);
+        let third = scoring.lock(4, TSpin::None, false, 1).unwrap();
+        assert!(!third.back_to_back);
+        assert_eq!(third.points, 800);
+    }
+
+    #[test]
+    fn combos_add_points_for_every_clear_in_a_row() {
+        let mut scoring = Scoring::new();
+        let points: Vec<(u32, u64)> = [1, 1, 2, 1]
+            .into_iter()
+            .map(|lines| scoring.lock(lines, TSpin::None, false, 3).unwrap())
+            .map(|action| (action.combo, action.points))
+            .collect();
+        assert_eq!(points, [(0, 300), (1, 450), (2, 1200), (3, 750)]);
+        scoring.lock(0, TSpin::None, false, 3);
+        assert_eq!(scoring.lock(1, TSpin::None, false, 3).unwrap().combo, 0);
+    }
+
+    #[test]
+    fn perfect_clears_add_a_bonus_by_lines() {
+        for (lines, points) in [(1, 900), (2, 1500), (3, 2300), (4, 2800)] {
+            let action = Scoring::new().lock(lines, TSpin::None, true, 1).unwrap();
+            assert!(action.perfect_clear);
+            assert_eq!(action.points, points);
+        }
+        let mut scoring = Scoring::new();
+        scoring.lock(4, TSpin::None, false, 1);
+        scoring.lock(0, TSpin::None, false, 1);
+        let action = scoring.lock(4, TSpin::None, true, 2).unwrap();
+        assert_eq!(action.describe(), "B2B TETRIS PERFECT CLEAR");
+        assert_eq!(action.points, (1200 + 3200) * 2);
+    }
+}
@@ -0,0 +1,147 @@
+use color_eyre::{eyre::{eyre, WrapErr}, Result};
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use tetris::bot::Difficulty;
+use tetris::config::GameConfig;
+use tetris::mode::GameMode;
+
+// Options chosen when the game is started.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub game: GameConfig,
+    // seed for every game, a new random one is picked for each game if not given
+    pub seed: Option<u64>,
+    // frames drawn per second, the game logic runs at a fixed rate regardless
+    pub frame_rate: u32,
+    // replay file to watch instead of playing
+    pub replay: Option<PathBuf>,
+    // play versus over the network, either waiting for a player on this port or
+    // connecting to the host at this address
+    pub host: Option<u16>,
+    pub connect: Option<String>,
+    // watch the games of the host at this address
+    pub spectate: Option<String>,
+    // how far behind the players the host keeps its spectators
+    pub spectator_delay: Duration,
+    // play versus against the computer
+    pub bot: Option<Difficulty>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            game: GameConfig::default(),
+            seed: None,
+            frame_rate: 60,
+            replay: None,
+            host: None,
+            connect: None,
+            spectate: None,
+            spectator_delay: Duration::ZERO,
+            bot: None,
+        }
+    }
+}
+
+impl Settings {
+
+    pub fn from_args<I>(args: I) -> Result<Settings>
+        where
+            I: IntoIterator<Item = String> {
+        let mut settings = Settings::default();
+        let game = &mut settings.game;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| eyre!("missing value for {arg}"));
+            match arg.as_str() {
+                "--seed" => {
+                    settings.seed = Some(value()?.parse().wrap_err("invalid --seed")?);
+                }
+                "--replay" => {
+                    settings.replay = Some(PathBuf::from(value()?));
+                }
+                "--host" => {
+                    settings.host = Some(value()?.parse().wrap_err("invalid --host")?);
+                }
+                "--connect" => {
+                    settings.connect = Some(value()?);
+                }
+                "--spectate" => {
+                    settings.spectate = Some(value()?);
+                }
+                "--spectator-delay" => {
+                    let seconds: u64 = value()?.parse().wrap_err("invalid --spectator-delay")?;
+                    settings.spectator_delay = Duration::from_secs(seconds);
+                }
+                "--bot" => {
+                    settings.bot = Some(value()?.parse().map_err(|err: String| eyre!(err))?);
+                }
+                "--mode" => {
+                    game.mode = value()?.parse().map_err(|err: String| eyre!(err))?;
+                }
+                "--time-limit" => {
+                    let seconds: u32 = value()?.parse().wrap_err("invalid --time-limit")?;
+                    game.time_limit_ms = seconds.saturating_mul(1000);
+                }
+                "--garbage-rows" => {
+                    game.garbage_rows = value()?.parse().wrap_err("invalid --garbage-rows")?;
+                }
+                "--garbage-interval" => {
+                    game.garbage_interval_ms = value()?.parse().wrap_err("invalid --garbage-interval")?;
+                }
+                "--garbage-delay" => {
+                    game.garbage_delay_ms = value()?.parse().wrap_err("invalid --garbage-delay")?;
+                }
+                "--garbage-messiness" => {
+                    let percent: u32 = value()?.parse().wrap_err("invalid --garbage-messiness")?;
+                    game.garbage_messiness = percent.min(100);
+                }
+                "--randomizer" => {
+                    game.randomizer = value()?.parse().map_err(|err: String| eyre!(err))?;
+                }
+                "--soft-drop-factor" => {
+                    game.soft_drop_factor = value()?.parse().wrap_err("invalid --soft-drop-factor")?;
+                }
+                "--fps" => {
+                    settings.frame_rate = value()?.parse().wrap_err("invalid --fps")?;
+                }
+                "--lock-delay" => {
+                    game.lock_delay_ms = value()?.parse().wrap_err("invalid --lock-delay")?;
+                }
+                "--lock-resets" => {
+                    game.lock_resets = value()?.parse().wrap_err("invalid --lock-resets")?;
+                }
+                "--das" => {
+                    game.das_ms = value()?.parse().wrap_err("invalid --das")?;
+                }
+                "--arr" => {
+                    game.arr_ms = value()?.parse().wrap_err("invalid --arr")?;
+                }
+                "--level" => {
+                    game.start_level = value()?.parse().wrap_err("invalid --level")?;
+                }
+                "--lines-per-level" => {
+                    game.lines_per_level = value()?.parse().wrap_err("invalid --lines-per-level")?;
+                }
+                "--next" => {
+                    game.queue_size = value()?.parse().wrap_err("invalid --next")?;
+                }
+                _ => return Err(eyre!("unknown argument {arg:?}")),
+            }
+        }
+        if settings.host.is_some() || settings.connect.is_some() || settings.spectate.is_some() || settings.bot.is_some() {
+            settings.game.mode = GameMode::Versus;
+        }
+        Ok(settings)
+    }
+
+    // The rules for the next game, with the seed filled in.
+    pub fn game_config(&self) -> GameConfig {
+        GameConfig {
+            seed: self.seed.unwrap_or_else(rand::random),
+            ..self.game.clone()
+        }
+    }
+}
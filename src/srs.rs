@@ -0,0 +1,199 @@
+use crate::board::Board;
+use crate::piece::Piece;
+
+// The four orientation states of the Super Rotation System.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rotation {
+    #[default]
+    Spawn,
+    Right,
+    Reverse,
+    Left,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RotationDirection {
+    Clockwise,
+    CounterClockwise,
+    Half,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum KickTable {
+    #[default]
+    JLSTZ,
+    I,
+    O,
+}
+
+impl Rotation {
+
+    // number of clockwise quarter turns away from the spawn state
+    pub fn turns(self) -> usize {
+        match self {
+            Rotation::Spawn => 0,
+            Rotation::Right => 1,
+            Rotation::Reverse => 2,
+            Rotation::Left => 3,
+        }
+    }
+
+    pub fn from_turns(turns: usize) -> Rotation {
+        match turns % 4 {
+            0 => Rotation::Spawn,
+            1 => Rotation::Right,
+            2 => Rotation::Reverse,
+            _ => Rotation::Left,
+        }
+    }
+
+    pub fn rotate(self, direction: RotationDirection) -> Rotation {
+        match direction {
+            RotationDirection::Clockwise => Rotation::from_turns(self.turns() + 1),
+            RotationDirection::CounterClockwise => Rotation::from_turns(self.turns() + 3),
+            RotationDirection::Half => Rotation::from_turns(self.turns() + 2),
+        }
+    }
+}
+
+const JLSTZ_KICKS: [[(i32, i32); 5]; 8] = [
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)], // 0 -> R
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],     // R -> 0
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],     // R -> 2
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)], // 2 -> R
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],    // 2 -> L
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],  // L -> 2
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],  // L -> 0
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],    // 0 -> L
+];
+
+const I_KICKS: [[(i32, i32); 5]; 8] = [
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)], // 0 -> R
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)], // R -> 0
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)], // R -> 2
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)], // 2 -> R
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)], // 2 -> L
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)], // L -> 2
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)], // L -> 0
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)], // 0 -> L
+];
+
+// 180 degree rotations are not part of the guideline, these follow the common SRS+ table.
+const HALF_KICKS: [[(i32, i32); 6]; 4] = [
+    [(0, 0), (0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)],    // 0 -> 2
+    [(0, 0), (1, 0), (1, 2), (1, 1), (0, 2), (0, 1)],      // R -> L
+    [(0, 0), (0, -1), (-1, -1), (1, -1), (-1, 0), (1, 0)], // 2 -> 0
+    [(0, 0), (-1, 0), (-1, 2), (-1, 1), (0, 2), (0, 1)],   // L -> R
+];
+
+// The offsets to try, in order, when rotating from one state into another.
+pub fn kicks(table: KickTable, from: Rotation, to: Rotation) -> &'static [(i32, i32)] {
+    if table == KickTable::O {
+        return &[(0, 0)];
+    }
+    if to == from.rotate(RotationDirection::Half) {
+        return &HALF_KICKS[from.turns()];
+    }
+    let index = match (from, to) {
+        (Rotation::Spawn, Rotation::Right) => 0,
+        (Rotation::Right, Rotation::Spawn) => 1,
+        (Rotation::Right, Rotation::Reverse) => 2,
+        (Rotation::Reverse, Rotation::Right) => 3,
+        (Rotation::Reverse, Rotation::Left) => 4,
+        (Rotation::Left, Rotation::Reverse) => 5,
+        (Rotation::Left, Rotation::Spawn) => 6,
+        (Rotation::Spawn, Rotation::Left) => 7,
+        _ => return &[(0, 0)],
+    };
+    match table {
+        KickTable::I => &I_KICKS[index],
+        _ => &JLSTZ_KICKS[index],
+    }
+}
+
+// Rotates the piece on the board, trying every kick of the table in order.
+// Returns the rotated piece and the index of the kick that was used.
+pub fn try_rotate(board: &Board, piece: &Piece, direction: RotationDirection) -> Option<(Piece, usize)> {
+    let to = piece.rotation.rotate(direction);
+    let rotated = piece.with_rotation(to);
+    kicks(piece.kind.kicks(), piece.rotation, to).iter().enumerate().find_map(|(i, (dx, dy))| {
+        let kicked = rotated.moved(*dx, *dy);
+        if board.fits(kicked.cells()) {
+            Some((kicked, i))
+        }
+        else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Cell;
+    use crate::piece::PieceKind;
+
+    fn piece(kind: PieceKind, rotation: Rotation, x: i32, y: i32) -> Piece {
+        Piece { kind, rotation, x, y }
+    }
+
+    #[test]
+    fn kicks_come_from_the_right_table() {
+        assert_eq!(kicks(KickTable::JLSTZ, Rotation::Spawn, Rotation::Right), &[(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)]);
+        assert_eq!(kicks(KickTable::JLSTZ, Rotation::Left, Rotation::Spawn), &[(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)]);
+        assert_eq!(kicks(KickTable::I, Rotation::Spawn, Rotation::Right), &[(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)]);
+        assert_eq!(kicks(KickTable::I, Rotation::Right, Rotation::Reverse), &[(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)]);
+        assert_eq!(kicks(KickTable::JLSTZ, Rotation::Spawn, Rotation::Reverse), &HALF_KICKS[0]);
+        assert_eq!(kicks(KickTable::I, Rotation::Right, Rotation::Left), &HALF_KICKS[1]);
+        assert_eq!(kicks(KickTable::O, Rotation::Spawn, Rotation::Right), &[(0, 0)]);
+    }
+
+    #[test]
+    fn free_rotations_use_the_first_kick() {
+        let board = Board::default();
+        let t = piece(PieceKind::T, Rotation::Spawn, 3, 5);
+        for direction in [RotationDirection::Clockwise, RotationDirection::CounterClockwise, RotationDirection::Half] {
+            let (rotated, kick) = try_rotate(&board, &t, direction).unwrap();
+            assert_eq!(kick, 0);
+            assert_eq!((rotated.x, rotated.y), (3, 5));
+            assert_eq!(rotated.rotation, Rotation::Spawn.rotate(direction));
+        }
+    }
+
+    #[test]
+    fn i_piece_kicks_off_the_walls() {
+        let board = Board::default();
+        // vertical in column 0, lying down has to move two cells right
+        let left = piece(PieceKind::I, Rotation::Right, -2, 0);
+        let (rotated, kick) = try_rotate(&board, &left, RotationDirection::CounterClockwise).unwrap();
+        assert_eq!((kick, rotated.x, rotated.y, rotated.rotation), (1, 0, 0, Rotation::Spawn));
+
+        // vertical in column 9, the first two kicks stay outside the board
+        let right = piece(PieceKind::I, Rotation::Left, 8, 0);
+        let (rotated, kick) = try_rotate(&board, &right, RotationDirection::Clockwise).unwrap();
+        assert_eq!((kick, rotated.x, rotated.y, rotated.rotation), (2, 6, 0, Rotation::Spawn));
+        assert!(rotated.cells().all(|(x, _)| (0..10).contains(&x)));
+    }
+
+    #[test]
+    fn t_piece_takes_the_last_kick_into_a_t_slot() {
+        // only the cells of the T and of the slot one left and two down are free,
+        // so every kick but the last one runs into the stack
+        let free = [(3, 2), (3, 3), (3, 4), (4, 3), (3, 5), (4, 5), (5, 5), (4, 6)];
+        let mut board = Board::default();
+        for y in 0..8 {
+            for x in 0..10 {
+                if !free.contains(&(x, y)) {
+                    board.set(x, y, Some(Cell::Garbage));
+                }
+            }
+        }
+        let t = piece(PieceKind::T, Rotation::Spawn, 3, 4);
+        assert!(board.fits(t.cells()));
+        let (rotated, kick) = try_rotate(&board, &t, RotationDirection::Clockwise).unwrap();
+        assert_eq!((kick, rotated.x, rotated.y, rotated.rotation), (4, 2, 2, Rotation::Right));
+
+        // nothing fits the other way round
+        assert_eq!(try_rotate(&board, &t, RotationDirection::CounterClockwise), None);
+    }
+}
@@ -1,18 +1,38 @@
 use std::io::{self, stdout, Stdout};
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use crossterm::{execute, terminal::*};
+use crossterm::{
+    event::{KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags},
+    execute,
+    terminal::*,
+};
 use ratatui::prelude::*;
 
 pub type Tui = Terminal<CrosstermBackend<Stdout>>;
 
+static KEYBOARD_ENHANCED: AtomicBool = AtomicBool::new(false);
+
 pub fn init () -> io::Result<Tui> {
     execute!(stdout(), EnterAlternateScreen)?;
     enable_raw_mode()?;
+    // terminals speaking the kitty keyboard protocol also report key releases
+    if supports_keyboard_enhancement().unwrap_or(false) {
+        execute!(stdout(), PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES))?;
+        KEYBOARD_ENHANCED.store(true, Ordering::Relaxed);
+    }
     Tui::new(CrosstermBackend::new(stdout()))
 }
 
 pub fn restore() -> io::Result<()> {
+    if KEYBOARD_ENHANCED.swap(false, Ordering::Relaxed) {
+        execute!(stdout(), PopKeyboardEnhancementFlags)?;
+    }
     execute!(stdout(), LeaveAlternateScreen)?;
     disable_raw_mode()?;
     Ok(())
-}
\ No newline at end of file
+}
+
+// Whether key release events are reported, so held keys can be tracked exactly.
+pub fn has_release_events() -> bool {
+    KEYBOARD_ENHANCED.load(Ordering::Relaxed)
+}
@@ -0,0 +1,296 @@
+use ratatui::{
+    prelude::*,
+    style::Color,
+    widgets::{canvas::{Canvas, Context, Rectangle}, Paragraph, *}
+};
+
+use tetris::board::Cell;
+use tetris::game::{Game, TICKS_PER_SECOND};
+use tetris::piece::{Piece, PieceKind};
+use tetris::scoring::ScoreAction;
+
+// width and height of a piece preview in board cells
+const PREVIEW_WIDTH: f64 = 6.0;
+const PREVIEW_HEIGHT: f64 = 4.0;
+// later pieces in the next queue are drawn smaller
+const QUEUE_SLOT_HEIGHT: f64 = 2.0;
+const QUEUE_SLOT_SCALE: f64 = 0.6;
+const PANEL_WIDTH: u16 = 14;
+
+// how long the description of a scoring action stays on screen
+const ACTION_FLASH_TICKS: u64 = 2 * TICKS_PER_SECOND as u64;
+
+// One player's game: the hold piece and stats on the left, the field in the middle and
+// the next queue on the right. Versus games draw one of these for each player.
+#[derive(Debug, Clone)]
+pub struct GameView<'a> {
+    game: &'a Game,
+    title: Option<String>,
+    stats: Vec<(&'static str, String)>,
+    last_action: Option<&'a (ScoreAction, u64)>,
+    // shown instead of the field, e.g. once the game is over
+    end_text: Option<Vec<Line<'a>>>,
+    paused: bool,
+    padding: f64,
+}
+
+impl<'a> GameView<'a> {
+
+    pub fn new(game: &'a Game) -> GameView<'a> {
+        GameView {
+            game,
+            title: None,
+            stats: vec![],
+            last_action: None,
+            end_text: None,
+            paused: false,
+            padding: 0.0,
+        }
+    }
+
+    // Written above the field.
+    pub fn title(mut self, title: impl Into<String>) -> GameView<'a> {
+        self.title = Some(title.into());
+        self
+    }
+
+    // The boxes below the hold piece as (title, value).
+    pub fn stats(mut self, stats: Vec<(&'static str, String)>) -> GameView<'a> {
+        self.stats = stats;
+        self
+    }
+
+    // The last scoring action and the tick it happened on, flashed for a while.
+    pub fn last_action(mut self, last_action: Option<&'a (ScoreAction, u64)>) -> GameView<'a> {
+        self.last_action = last_action;
+        self
+    }
+
+    pub fn end_text(mut self, end_text: Vec<Line<'a>>) -> GameView<'a> {
+        self.end_text = Some(end_text);
+        self
+    }
+
+    pub fn paused(mut self, paused: bool) -> GameView<'a> {
+        self.paused = paused;
+        self
+    }
+
+    // space between the blocks of a piece, in cells
+    pub fn padding(mut self, padding: f64) -> GameView<'a> {
+        self.padding = padding;
+        self
+    }
+
+    // Splits `area` into the left panel, the field and the right panel.
+    pub fn areas(&self, area: Rect) -> [Rect; 3] {
+        // every cell is two characters wide so that it looks square
+        let board = self.game.board();
+        let field_width = board.width() as u16 * 2 + 2;
+        let field_height = board.visible_height() as u16 + 2;
+        let [left, field, right] = Layout::horizontal([
+                Constraint::Length(PANEL_WIDTH),
+                Constraint::Length(field_width),
+                Constraint::Length(PANEL_WIDTH),
+            ])
+            .flex(layout::Flex::Center)
+            .spacing(1)
+            .areas(area);
+        let [field] = Layout::vertical([Constraint::Length(field_height)])
+            .flex(layout::Flex::Center)
+            .areas(field);
+        [left, field, right]
+    }
+
+    fn render_field(&self, area: Rect, buf: &mut Buffer) {
+        let mut block = Block::bordered();
+        if let Some(title) = &self.title {
+            block = block.title(title.as_str()).title_alignment(Alignment::Center);
+        }
+
+        if let Some(end_text) = &self.end_text {
+            Paragraph::new(end_text.clone())
+                .block(block)
+                .alignment(Alignment::Center)
+                .wrap(Wrap { trim: true })
+                .red()
+                .render(area, buf);
+            return;
+        }
+
+        let board = self.game.board();
+        let current_piece = self.game.current_piece();
+        Canvas::default()
+            .block(block.clone())
+            .x_bounds([0.0, board.width() as f64])
+            .y_bounds([0.0, board.visible_height() as f64])
+            .background_color(Color::Black)
+            .paint(|ctx| {
+                for (x, y) in self.game.ghost_piece().cells() {
+                    self.draw_cell(ctx, x as f64, y as f64, Color::DarkGray);
+                }
+                ctx.layer();
+                for (x, y) in current_piece.cells() {
+                    self.draw_cell(ctx, x as f64, y as f64, piece_color(current_piece.kind));
+                }
+                ctx.layer();
+                for (x, y, cell) in board.occupied() {
+                    self.draw_cell(ctx, x as f64, y as f64, cell_color(cell));
+                }
+            })
+            .render(area, buf);
+
+        if self.paused {
+            Paragraph::new(Line::from("Paused"))
+                .block(block)
+                .centered()
+                .bold()
+                .render(area, buf);
+        }
+    }
+
+    // A bar in the gap left of the field with a cell per incoming row of garbage, oldest at
+    // the bottom. Rows still waiting for their delay are yellow, rows ready to rise red.
+    fn render_meter(&self, field: Rect, buf: &mut Buffer) {
+        if field.x == 0 || field.height < 2 {
+            return;
+        }
+        let x = field.x - 1;
+        let bottom = field.bottom() - 2;
+        let rows = self.game.incoming_garbage().iter()
+            .flat_map(|garbage| std::iter::repeat_n(garbage.is_ready(), garbage.rows as usize));
+        for (i, ready) in rows.take(field.height as usize - 2).enumerate() {
+            let color = if ready { Color::Red } else { Color::Yellow };
+            buf.get_mut(x, bottom - i as u16).set_symbol("█").set_fg(color);
+        }
+    }
+
+    // Draws a single piece in its spawn state centered in a small bordered canvas.
+    fn render_preview(&self, title: &str, piece: Option<PieceKind>, dimmed: bool, area: Rect, buf: &mut Buffer) {
+        Canvas::default()
+            .block(Block::bordered().title(title))
+            .x_bounds([0.0, PREVIEW_WIDTH])
+            .y_bounds([0.0, PREVIEW_HEIGHT])
+            .background_color(Color::Black)
+            .paint(|ctx| {
+                let Some(kind) = piece else {
+                    return;
+                };
+                let color = if dimmed { Color::DarkGray } else { piece_color(kind) };
+                self.draw_centered(ctx, kind, 0.0, PREVIEW_HEIGHT, 1.0, color);
+            })
+            .render(area, buf);
+    }
+
+    fn render_queue(&self, area: Rect, buf: &mut Buffer) {
+        let queue_size = self.game.queue().count().max(1);
+        let height = PREVIEW_HEIGHT + QUEUE_SLOT_HEIGHT * (queue_size - 1) as f64;
+        Canvas::default()
+            .block(Block::bordered().title(" Next "))
+            .x_bounds([0.0, PREVIEW_WIDTH])
+            .y_bounds([0.0, height])
+            .background_color(Color::Black)
+            .paint(|ctx| {
+                let mut top = height;
+                for (i, kind) in self.game.queue().enumerate() {
+                    if i == 0 {
+                        top -= PREVIEW_HEIGHT;
+                        self.draw_centered(ctx, kind, top, PREVIEW_HEIGHT, 1.0, piece_color(kind));
+                    }
+                    else {
+                        top -= QUEUE_SLOT_HEIGHT;
+                        self.draw_centered(ctx, kind, top, QUEUE_SLOT_HEIGHT, QUEUE_SLOT_SCALE, piece_color(kind));
+                    }
+                }
+            })
+            .render(area, buf);
+    }
+
+    // Draws a piece in its spawn state centered in a PREVIEW_WIDTH wide slot starting at `bottom`.
+    fn draw_centered(&self, ctx: &mut Context, kind: PieceKind, bottom: f64, height: f64, scale: f64, color: Color) {
+        let blocks: Vec<(i32, i32)> = Piece::new(kind).blocks().collect();
+        let min_x = blocks.iter().map(|(x, _)| *x).min().unwrap_or(0) as f64;
+        let max_x = blocks.iter().map(|(x, _)| *x).max().unwrap_or(0) as f64;
+        let min_y = blocks.iter().map(|(_, y)| *y).min().unwrap_or(0) as f64;
+        let max_y = blocks.iter().map(|(_, y)| *y).max().unwrap_or(0) as f64;
+        let offset_x = (PREVIEW_WIDTH - (max_x - min_x + 1.0) * scale) / 2.0 - min_x * scale;
+        let offset_y = bottom + (height - (max_y - min_y + 1.0) * scale) / 2.0 - min_y * scale;
+        for (x, y) in blocks {
+            self.draw_scaled_cell(ctx, x as f64 * scale + offset_x, y as f64 * scale + offset_y, scale, color);
+        }
+    }
+
+    fn draw_cell(&self, ctx: &mut Context, x: f64, y: f64, color: Color) {
+        self.draw_scaled_cell(ctx, x, y, 1.0, color);
+    }
+
+    fn draw_scaled_cell(&self, ctx: &mut Context, x: f64, y: f64, size: f64, color: Color) {
+        let padding = self.padding * size;
+        ctx.draw(&Rectangle {
+            x: x + padding,
+            y: y + padding,
+            width: size - 2.0 * padding,
+            height: size - 2.0 * padding,
+            color,
+        });
+    }
+}
+
+impl Widget for GameView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let [left, field, right] = self.areas(area);
+
+        let mut constraints = vec![Constraint::Length(PREVIEW_HEIGHT as u16 + 2)];
+        constraints.extend(self.stats.iter().map(|_| Constraint::Length(3)));
+        constraints.push(Constraint::Fill(1));
+        let areas = Layout::vertical(constraints).split(left);
+        let (hold, action) = (areas[0], areas[areas.len() - 1]);
+        self.render_preview(" Hold ", self.game.held_piece(), self.game.hold_used(), hold, buf);
+
+        for ((title, value), area) in self.stats.iter().zip(areas[1..].iter()) {
+            Paragraph::new(Line::from(value.as_str().bold()))
+                .block(Block::bordered().title(*title))
+                .right_aligned()
+                .render(*area, buf);
+        }
+
+        if let Some((last_action, tick)) = self.last_action {
+            if self.game.ticks().saturating_sub(*tick) < ACTION_FLASH_TICKS {
+                Paragraph::new(Line::from(last_action.describe().bold().yellow()))
+                    .wrap(Wrap { trim: true })
+                    .centered()
+                    .render(action.inner(&layout::Margin::new(0, 1)), buf);
+            }
+        }
+
+        let queue_size = self.game.queue().count().max(1);
+        let queue_height = PREVIEW_HEIGHT + QUEUE_SLOT_HEIGHT * (queue_size - 1) as f64;
+        let [next, _] = Layout::vertical([Constraint::Length(queue_height as u16 + 2), Constraint::Fill(1)])
+            .areas(right);
+        self.render_queue(next, buf);
+
+        self.render_field(field, buf);
+        if self.game.mode().is_versus() {
+            self.render_meter(field, buf);
+        }
+    }
+}
+
+pub fn piece_color(kind: PieceKind) -> Color {
+    match kind {
+        PieceKind::I => Color::Cyan,
+        PieceKind::O => Color::Yellow,
+        PieceKind::T => Color::Magenta,
+        PieceKind::S => Color::Green,
+        PieceKind::Z => Color::Red,
+        PieceKind::J => Color::Blue,
+        PieceKind::L => Color::Rgb(255, 165, 0),
+    }
+}
+
+pub fn cell_color(cell: Cell) -> Color {
+    match cell {
+        Cell::Piece(kind) => piece_color(kind),
+        Cell::Garbage => Color::Gray,
+    }
+}